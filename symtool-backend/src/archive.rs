@@ -0,0 +1,538 @@
+//! Utilities for archives of objects.
+//!
+//! Objects in archives are usually edited in place, but edits that change the size of a member
+//! require rewriting the archive with [`Archive`].
+
+use crate::error::{Error, Result};
+use crate::object::{is_known_object, parse_object, ArchiveKind};
+use crate::patch::Patch;
+use crate::symbol::{Binding, Kind};
+use goblin::mach::fat::FAT_MAGIC;
+use goblin::mach::MultiArch;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+const MAGIC: &[u8] = b"!<arch>\n";
+const HEADER_SIZE: usize = 60;
+
+/// The fields of a member header, as byte ranges relative to the start of the header.
+const NAME: Range<usize> = 0..16;
+const DATE: Range<usize> = 16..28;
+const UID: Range<usize> = 28..34;
+const GID: Range<usize> = 34..40;
+const MODE: Range<usize> = 40..48;
+const SIZE: Range<usize> = 48..58;
+
+/// Returns the offset of every member header in an archive and the size of the member, including
+/// the symbol table and the GNU long name table.
+fn member_headers(archive: &[u8]) -> Result<Vec<(usize, usize)>> {
+    let mut headers = Vec::new();
+    let mut offset = MAGIC.len();
+    while offset < archive.len() {
+        let header = archive
+            .get(offset..offset + HEADER_SIZE)
+            .ok_or_else(|| Error::Malformed("truncated archive member header".to_string()))?;
+        let size = std::str::from_utf8(&header[SIZE])
+            .ok()
+            .and_then(|size| size.trim_end().parse::<usize>().ok())
+            .ok_or_else(|| Error::Malformed("invalid archive member size".to_string()))?;
+        headers.push((offset, size));
+
+        // Members are aligned to two bytes
+        offset += HEADER_SIZE + size + size % 2;
+    }
+    Ok(headers)
+}
+
+/// Returns true if the member is the symbol table, or the GNU long name table.
+fn is_special(archive: &[u8], header: usize) -> bool {
+    let name = &archive[header + NAME.start..header + NAME.end];
+    let data = &archive[(header + HEADER_SIZE).min(archive.len())..];
+    let symdef = b"__.SYMDEF";
+    name.starts_with(b"/ ")
+        || name.starts_with(b"//")
+        || name.starts_with(b"/SYM64/")
+        || name.starts_with(symdef)
+        || (name.starts_with(b"#1/") && data.starts_with(symdef))
+}
+
+/// Returns a patch that replaces a header field with `value`, unless the field is blank.
+fn replace_field(
+    archive: &[u8],
+    base: usize,
+    header: usize,
+    field: Range<usize>,
+    value: &str,
+) -> Option<Patch> {
+    let range = header + field.start..header + field.end;
+    if archive[range.clone()].iter().all(|b| *b == b' ') {
+        return None;
+    }
+    let mut data = vec![b' '; field.len()];
+    data[..value.len()].copy_from_slice(value.as_bytes());
+    if archive[range.clone()] == data[..] {
+        None
+    } else {
+        Some(Patch::at(base + range.start, data))
+    }
+}
+
+/// Returns the patches that make the member headers of an archive deterministic.
+fn deterministic_archive(archive: &[u8], base: usize, patches: &mut Vec<Patch>) -> Result<()> {
+    for (header, _) in member_headers(archive)? {
+        let mut fields = vec![(DATE, "0"), (UID, "0"), (GID, "0")];
+        if !is_special(archive, header) {
+            fields.push((MODE, "644"));
+        }
+        for (field, value) in fields {
+            patches.extend(replace_field(archive, base, header, field, value));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the archive(s) in a binary and their offsets, including the archives in Mach-O fat
+/// binaries.
+fn archives(object: &[u8]) -> Result<Vec<(usize, &[u8])>> {
+    let mut archives = Vec::new();
+    if goblin::mach::peek(object, 0).ok() == Some(FAT_MAGIC) {
+        if let Ok(fat) = MultiArch::new(object) {
+            for arch in fat.arches()? {
+                let offset = arch.offset as usize;
+                let slice = object
+                    .get(offset..offset + arch.size as usize)
+                    .ok_or_else(|| {
+                        Error::Malformed(
+                            "fat binary slice extends past the end of the file".to_string(),
+                        )
+                    })?;
+                if slice.starts_with(MAGIC) {
+                    archives.push((offset, slice));
+                }
+            }
+            return Ok(archives);
+        }
+    }
+    if object.starts_with(MAGIC) {
+        archives.push((0, object));
+    }
+    Ok(archives)
+}
+
+/// Returns the patches that make the member headers of the archive(s) in a binary deterministic,
+/// like `ar D`.
+///
+/// The timestamps, user IDs, and group IDs of every member are set to 0, and the modes of members
+/// other than the symbol table and long name table are set to 644.  Archives in Mach-O fat
+/// binaries are also patched.  Returns no patches for binaries that are not archives.
+pub fn deterministic_headers(object: &[u8]) -> Result<Vec<Patch>> {
+    let mut patches = Vec::new();
+    for (base, archive) in archives(object)? {
+        deterministic_archive(archive, base, &mut patches)?;
+    }
+    Ok(patches)
+}
+
+/// Returns the byte ranges of the timestamp, user ID, group ID, and mode of every member header
+/// in the archive(s) in a binary.
+pub(crate) fn metadata_ranges(object: &[u8]) -> Result<Vec<Range<usize>>> {
+    let mut ranges = Vec::new();
+    for (base, archive) in archives(object)? {
+        for (header, _) in member_headers(archive)? {
+            ranges.push(base + header + DATE.start..base + header + MODE.end);
+        }
+    }
+    Ok(ranges)
+}
+
+/// Returns true if `bytes` is an archive, rather than an object or a fat binary.
+pub(crate) fn is_archive(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// A member of an archive, other than the symbol table or the GNU long name table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveMember {
+    /// The name of the member
+    pub name: String,
+
+    /// The modification time, as written in the member header
+    pub date: String,
+
+    /// The owner's user ID, as written in the member header
+    pub uid: String,
+
+    /// The owner's group ID, as written in the member header
+    pub gid: String,
+
+    /// The file mode in octal, as written in the member header
+    pub mode: String,
+
+    /// The contents of the member
+    pub data: Vec<u8>,
+}
+
+/// An archive that can be modified and written again.
+///
+/// Members may be resized, added, removed, or reordered.  The long name table and the symbol
+/// table are regenerated when writing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Archive {
+    /// The format of the archive
+    pub kind: ArchiveKind,
+
+    /// The members, in the order they are stored
+    pub members: Vec<ArchiveMember>,
+
+    /// Whether the archive has a symbol table
+    pub symbol_table: bool,
+}
+
+/// Returns a header field as a string, without padding.
+fn header_field(header: &[u8], field: Range<usize>) -> String {
+    String::from_utf8_lossy(&header[field])
+        .trim_end_matches(' ')
+        .to_string()
+}
+
+/// Returns the GNU long name at `offset` in the long name table.
+fn long_name(names: Option<&[u8]>, offset: &str) -> Result<String> {
+    let malformed = || Error::Malformed(format!("invalid long member name /{}", offset));
+    let names = names.ok_or_else(malformed)?;
+    let name = offset
+        .parse::<usize>()
+        .ok()
+        .and_then(|offset| names.get(offset..))
+        .ok_or_else(malformed)?;
+    let end = name
+        .windows(2)
+        .position(|terminator| terminator == b"/\n")
+        .ok_or_else(malformed)?;
+    Ok(String::from_utf8_lossy(&name[..end]).to_string())
+}
+
+impl Archive {
+    /// Parse a GNU or BSD archive.
+    ///
+    /// Archives in Mach-O fat binaries must be parsed separately.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(Error::Malformed("not an archive".to_string()));
+        }
+        let mut kind = ArchiveKind::Bsd;
+        let mut members = Vec::new();
+        let mut symbol_table = false;
+        let mut long_names = None;
+        for (offset, size) in member_headers(bytes)? {
+            let header = &bytes[offset..offset + HEADER_SIZE];
+            let data = bytes
+                .get(offset + HEADER_SIZE..offset + HEADER_SIZE + size)
+                .ok_or_else(|| {
+                    Error::Malformed("archive member extends past the end of the file".to_string())
+                })?;
+            let raw_name = header_field(header, NAME);
+            if raw_name.starts_with('/') || raw_name.ends_with('/') {
+                kind = ArchiveKind::Gnu;
+            }
+            let (name, data) = match raw_name.as_str() {
+                "/" | "/SYM64/" => {
+                    symbol_table = true;
+                    continue;
+                }
+                "//" => {
+                    long_names = Some(data);
+                    continue;
+                }
+                name if name.starts_with("#1/") => {
+                    let length = name[3..]
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|length| *length <= data.len())
+                        .ok_or_else(|| {
+                            Error::Malformed(format!("invalid long member name {}", name))
+                        })?;
+                    let (name, data) = data.split_at(length);
+                    let end = name.iter().position(|b| *b == 0).unwrap_or(length);
+                    (String::from_utf8_lossy(&name[..end]).to_string(), data)
+                }
+                name if name.starts_with('/') => (long_name(long_names, &name[1..])?, data),
+                name => (name.trim_end_matches('/').to_string(), data),
+            };
+            if name.starts_with("__.SYMDEF") {
+                symbol_table = true;
+                continue;
+            }
+            members.push(ArchiveMember {
+                name,
+                date: header_field(header, DATE),
+                uid: header_field(header, UID),
+                gid: header_field(header, GID),
+                mode: header_field(header, MODE),
+                data: data.to_vec(),
+            });
+        }
+        Ok(Self {
+            kind,
+            members,
+            symbol_table,
+        })
+    }
+
+    /// Zero the timestamps, user IDs, and group IDs of the members and set their modes to 644,
+    /// like `ar D`.
+    pub fn make_deterministic(&mut self) {
+        for member in &mut self.members {
+            member.date = "0".to_string();
+            member.uid = "0".to_string();
+            member.gid = "0".to_string();
+            member.mode = "644".to_string();
+        }
+    }
+
+    /// The defined global and weak symbols of each member, in the order they are stored.
+    ///
+    /// Members that aren't ELF or Mach-O objects define no symbols.
+    fn defined_symbols(&self) -> Result<Vec<Vec<String>>> {
+        self.members
+            .iter()
+            .map(|member| {
+                let mut names = Vec::new();
+                if !is_known_object(&member.data) {
+                    return Ok(names);
+                }
+                let object = parse_object(&member.data).map_err(|e| {
+                    Error::Malformed(format!("archive member {}: {}", member.name, e))
+                })?;
+                for symbol in crate::symbol::symbols(&member.data, &object)? {
+                    match symbol.name() {
+                        Some(name)
+                            if !name.is_empty()
+                                && symbol.is_defined()
+                                && symbol.binding() != Binding::Local
+                                && !matches!(symbol.kind(), Kind::Section | Kind::File) =>
+                        {
+                            names.push(name.value.to_string())
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(names)
+            })
+            .collect()
+    }
+
+    /// Write the archive.
+    ///
+    /// GNU archives store names longer than 15 characters in a long name table.  BSD archives
+    /// store every name before the contents of the member, padded so the contents are aligned to
+    /// 8 bytes, like `llvm-ar` and Apple's `libtool`.  If the archive has a symbol table, it is
+    /// regenerated, listing the defined global and weak symbols of each object in the order they
+    /// are stored.
+    pub fn write(&self) -> Result<Vec<u8>> {
+        let symbols = if self.symbol_table {
+            Some(self.defined_symbols()?)
+        } else {
+            None
+        };
+        match self.kind {
+            ArchiveKind::Gnu => self.write_gnu(symbols),
+            ArchiveKind::Bsd => self.write_bsd(symbols),
+        }
+    }
+
+    fn write_gnu(&self, symbols: Option<Vec<Vec<String>>>) -> Result<Vec<u8>> {
+        // Long names are stored in the long name table, terminated by "/\n"
+        let mut long_names = Vec::new();
+        let names: Vec<_> = self
+            .members
+            .iter()
+            .map(|member| {
+                if member.name.len() > 15 || member.name.contains('/') {
+                    let name = format!("/{}", long_names.len());
+                    long_names.extend_from_slice(member.name.as_bytes());
+                    long_names.extend_from_slice(b"/\n");
+                    name
+                } else {
+                    format!("{}/", member.name)
+                }
+            })
+            .collect();
+        if long_names.len() % 2 != 0 {
+            long_names.push(b'\n');
+        }
+
+        // The symbol table lists the offset of the header of the member defining each symbol
+        let symbol_table_size = symbols.as_ref().map(|symbols| {
+            let names = symbols.iter().flatten();
+            let size = 4 + names.clone().map(|name| 4 + name.len() + 1).sum::<usize>();
+            size + size % 2
+        });
+        let mut offset = MAGIC.len();
+        if let Some(size) = symbol_table_size {
+            offset += HEADER_SIZE + size;
+        }
+        if !long_names.is_empty() {
+            offset += HEADER_SIZE + long_names.len();
+        }
+        let mut member_offsets = Vec::new();
+        for member in &self.members {
+            member_offsets.push(symbol_table_offset(offset)?);
+            offset += HEADER_SIZE + member.data.len() + member.data.len() % 2;
+        }
+
+        let mut out = MAGIC.to_vec();
+        if let (Some(symbols), Some(size)) = (&symbols, symbol_table_size) {
+            push_header(&mut out, "/", "0", "0", "0", "0", size);
+            let count = symbols.iter().flatten().count() as u32;
+            out.extend_from_slice(&count.to_be_bytes());
+            for (names, offset) in symbols.iter().zip(&member_offsets) {
+                for _ in names {
+                    out.extend_from_slice(&offset.to_be_bytes());
+                }
+            }
+            for name in symbols.iter().flatten() {
+                out.extend_from_slice(name.as_bytes());
+                out.push(0);
+            }
+            if out.len() % 2 != 0 {
+                out.push(0);
+            }
+        }
+        if !long_names.is_empty() {
+            push_header(&mut out, "//", "", "", "", "", long_names.len());
+            out.extend_from_slice(&long_names);
+        }
+        for (member, name) in self.members.iter().zip(&names) {
+            push_header(
+                &mut out,
+                name,
+                &member.date,
+                &member.uid,
+                &member.gid,
+                &member.mode,
+                member.data.len(),
+            );
+            out.extend_from_slice(&member.data);
+            if out.len() % 2 != 0 {
+                out.push(b'\n');
+            }
+        }
+        Ok(out)
+    }
+
+    fn write_bsd(&self, symbols: Option<Vec<Vec<String>>>) -> Result<Vec<u8>> {
+        let mut offset = MAGIC.len();
+        let symbol_table_size = symbols.as_ref().map(|symbols| {
+            let names = symbols.iter().flatten();
+            let count = names.clone().count();
+            let strings = names.map(|name| name.len() + 1).sum::<usize>();
+            let size = 4 + 8 * count + 4 + strings;
+            size + padding(size)
+        });
+        let symbol_table_name = "__.SYMDEF";
+        if let Some(size) = symbol_table_size {
+            offset += HEADER_SIZE + bsd_name_size(offset, symbol_table_name) + size;
+        }
+        let mut member_offsets = Vec::new();
+        for member in &self.members {
+            member_offsets.push(symbol_table_offset(offset)?);
+            offset += HEADER_SIZE
+                + bsd_name_size(offset, &member.name)
+                + member.data.len()
+                + padding(member.data.len());
+        }
+
+        let mut out = MAGIC.to_vec();
+        if let (Some(symbols), Some(size)) = (&symbols, symbol_table_size) {
+            // Symbol table entries are the offsets of each symbol name and of the header of the
+            // member defining it
+            let mut entries = Vec::new();
+            let mut strings = Vec::new();
+            for (names, offset) in symbols.iter().zip(&member_offsets) {
+                for name in names {
+                    entries.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+                    entries.extend_from_slice(&offset.to_le_bytes());
+                    strings.extend_from_slice(name.as_bytes());
+                    strings.push(0);
+                }
+            }
+            let mut data = Vec::with_capacity(size);
+            data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            data.extend_from_slice(&entries);
+            data.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+            data.extend_from_slice(&strings);
+            data.resize(size, 0);
+            push_bsd_member(&mut out, symbol_table_name, ["0", "0", "0", "0"], &data);
+        }
+        for member in &self.members {
+            let fields = [member.date.as_str(), &member.uid, &member.gid, &member.mode];
+            push_bsd_member(&mut out, &member.name, fields, &member.data);
+        }
+        Ok(out)
+    }
+}
+
+/// Returns the number of bytes needed to align `size` to 8 bytes.
+fn padding(size: usize) -> usize {
+    (8 - size % 8) % 8
+}
+
+/// Returns the size of a BSD member name written at `offset`, padded so the contents that follow
+/// are aligned to 8 bytes.
+fn bsd_name_size(offset: usize, name: &str) -> usize {
+    name.len() + padding(offset + HEADER_SIZE + name.len())
+}
+
+/// Append a BSD member, with its name before the contents, and padding after the contents so the
+/// next header is aligned to 8 bytes.
+fn push_bsd_member(out: &mut Vec<u8>, name: &str, fields: [&str; 4], data: &[u8]) {
+    let name_size = bsd_name_size(out.len(), name);
+    let padding = padding(data.len());
+    let [date, uid, gid, mode] = fields;
+    push_header(
+        out,
+        &format!("#1/{}", name_size),
+        date,
+        uid,
+        gid,
+        mode,
+        name_size + data.len() + padding,
+    );
+    out.extend_from_slice(name.as_bytes());
+    out.resize(out.len() + name_size - name.len(), 0);
+    out.extend_from_slice(data);
+    out.resize(out.len() + padding, b'\n');
+}
+
+/// Returns a member offset for a 32-bit symbol table.
+fn symbol_table_offset(offset: usize) -> Result<u32> {
+    u32::try_from(offset)
+        .map_err(|_| Error::Unsupported("symbol tables of archives larger than 4 GiB".to_string()))
+}
+
+/// Append a member header.
+fn push_header(
+    out: &mut Vec<u8>,
+    name: &str,
+    date: &str,
+    uid: &str,
+    gid: &str,
+    mode: &str,
+    size: usize,
+) {
+    let size = size.to_string();
+    let fields = [
+        (name, NAME),
+        (date, DATE),
+        (uid, UID),
+        (gid, GID),
+        (mode, MODE),
+        (size.as_str(), SIZE),
+    ];
+    for (value, field) in fields {
+        let width = field.len();
+        out.extend_from_slice(value.as_bytes());
+        out.resize(out.len() + width.saturating_sub(value.len()), b' ');
+    }
+    out.extend_from_slice(b"`\n");
+}
@@ -0,0 +1,133 @@
+//! Check the symbols exported by a binary against a policy.
+
+use crate::diff::SymbolInfo;
+use crate::editor::{is_match, regex_set};
+use crate::error::{Error, Result};
+use crate::object::{inspect_object, Context, Object, TransformOptions};
+use crate::symbol::{Binding, Kind, Visibility};
+
+/// A policy of which symbols may be exported.
+///
+/// Symbols are exported if they are defined, global or weak, and not hidden.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl Policy {
+    /// Create a policy that allows every symbol.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow exporting symbols with names matching the regex `pattern`.
+    ///
+    /// If any patterns are allowed, symbols that don't match one of them may not be exported.
+    pub fn allow(mut self, pattern: &str) -> Self {
+        self.allow.push(pattern.to_string());
+        self
+    }
+
+    /// Deny exporting symbols with names matching the regex `pattern`.
+    ///
+    /// Takes precedence over [`allow`](Policy::allow).
+    pub fn deny(mut self, pattern: &str) -> Self {
+        self.deny.push(pattern.to_string());
+        self
+    }
+}
+
+/// The reason an exported symbol violates a policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The symbol matches the denied pattern
+    Denied(String),
+
+    /// The symbol doesn't match any allowed pattern
+    NotAllowed,
+}
+
+impl std::fmt::Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Denied(pattern) => write!(f, "denied by pattern \"{}\"", pattern),
+            Self::NotAllowed => write!(f, "not allowed"),
+        }
+    }
+}
+
+/// An exported symbol that violates a policy.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// The location of the object exporting the symbol within an archive or fat binary
+    pub context: Context,
+
+    /// The exported symbol
+    pub symbol: SymbolInfo,
+
+    /// Why the symbol may not be exported
+    pub reason: Reason,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.context.is_nested() {
+            write!(f, "{}: ", self.context)?;
+        }
+        write!(f, "{} is exported but {}", self.symbol, self.reason)
+    }
+}
+
+/// Check the symbols exported by a binary or archive of binaries against `policy`.
+///
+/// The violations are returned in the order the symbols are stored.
+pub fn check(bytes: &[u8], options: &TransformOptions, policy: &Policy) -> Result<Vec<Violation>> {
+    let allow = regex_set(&policy.allow)?;
+    let deny = regex_set(&policy.deny)?;
+    let objects = inspect_object(
+        bytes,
+        options,
+        &|bytes: &[u8], object: Object, _: &Context| {
+            let mut violations = Vec::new();
+            for symbol in crate::symbol::symbols(bytes, &object)? {
+                let name = match symbol.name() {
+                    Some(name) if !name.is_empty() => name.value,
+                    _ => continue,
+                };
+                let exported = symbol.is_defined()
+                    && symbol.binding() != Binding::Local
+                    && matches!(
+                        symbol.visibility(),
+                        Visibility::Default | Visibility::Protected
+                    )
+                    && !matches!(symbol.kind(), Kind::Section | Kind::File | Kind::Debug);
+                if !exported {
+                    continue;
+                }
+                let denied = deny
+                    .as_ref()
+                    .and_then(|deny| deny.matches(name).iter().next());
+                let reason = match denied {
+                    Some(index) => Reason::Denied(policy.deny[index].clone()),
+                    None if allow.is_some() && !is_match(&allow, name) => Reason::NotAllowed,
+                    None => continue,
+                };
+                violations.push((SymbolInfo::new(name, &symbol), reason));
+            }
+            Ok::<_, Error>(violations)
+        },
+    )?;
+    Ok(objects
+        .into_iter()
+        .flat_map(|(context, violations)| {
+            violations
+                .into_iter()
+                .map(move |(symbol, reason)| Violation {
+                    context: context.clone(),
+                    symbol,
+                    reason,
+                })
+        })
+        .collect())
+}
@@ -0,0 +1,181 @@
+//! Compare the symbols exported by two binaries.
+
+use crate::error::{Error, Result};
+use crate::object::{inspect_object, Context, Object, TransformOptions};
+use crate::symbol::{Binding, Kind, Symbol, Visibility};
+use std::collections::BTreeMap;
+
+/// The properties of a symbol that are compared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub binding: Binding,
+    pub kind: Kind,
+    pub visibility: Visibility,
+}
+
+impl SymbolInfo {
+    /// The properties of `symbol`, which is named `name`.
+    pub fn new(name: &str, symbol: &Symbol) -> Self {
+        Self {
+            name: name.to_string(),
+            binding: symbol.binding(),
+            kind: symbol.kind(),
+            visibility: symbol.visibility(),
+        }
+    }
+}
+
+impl std::fmt::Display for SymbolInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {}, {})",
+            self.name, self.binding, self.kind, self.visibility
+        )
+    }
+}
+
+/// A difference between the symbols exported by two objects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SymbolDiff {
+    /// The symbol is only exported by the new object
+    Added(SymbolInfo),
+
+    /// The symbol is only exported by the old object
+    Removed(SymbolInfo),
+
+    /// The binding, kind, or visibility of the symbol changed
+    Changed { old: SymbolInfo, new: SymbolInfo },
+}
+
+/// The differences between corresponding objects in two binaries.
+///
+/// Objects correspond if they are in the same fat binary slice and archive member.  If an
+/// archive contains several members with the same name, they correspond in order.
+#[derive(Clone, Debug)]
+pub struct ObjectDiff {
+    /// The architecture of the fat binary slice containing the objects, if any
+    pub architecture: Option<String>,
+
+    /// The name of the archive member containing the objects, if any
+    pub member: Option<String>,
+
+    /// The differences, sorted by symbol name
+    pub symbols: Vec<SymbolDiff>,
+}
+
+/// Identifies corresponding objects: the architecture, member name, and occurrence of the name.
+type ObjectKey = (Option<String>, Option<String>, usize);
+
+/// The defined symbols of each object, by name.
+fn defined_symbols(
+    bytes: &[u8],
+    options: &TransformOptions,
+) -> Result<Vec<(ObjectKey, BTreeMap<String, SymbolInfo>)>> {
+    let objects = inspect_object(
+        bytes,
+        options,
+        &|bytes: &[u8], object: Object, _: &Context| {
+            let mut symbols = BTreeMap::new();
+            for symbol in crate::symbol::symbols(bytes, &object)? {
+                let name = match symbol.name() {
+                    Some(name) if !name.is_empty() => name.value,
+                    _ => continue,
+                };
+                if !symbol.is_defined()
+                    || matches!(symbol.kind(), Kind::Section | Kind::File | Kind::Debug)
+                {
+                    continue;
+                }
+                let info = SymbolInfo::new(name, &symbol);
+
+                // Local symbols may share a name with each other or with a global symbol
+                let external = info.binding != Binding::Local;
+                let existing = symbols
+                    .get(name)
+                    .map(|s: &SymbolInfo| s.binding != Binding::Local);
+                if existing.is_none() || (external && existing == Some(false)) {
+                    symbols.insert(name.to_string(), info);
+                }
+            }
+            Ok::<_, Error>(symbols)
+        },
+    )?;
+
+    let mut occurrences = BTreeMap::new();
+    Ok(objects
+        .into_iter()
+        .map(|(context, symbols)| {
+            let architecture = context.fat_arch.map(|arch| match context.architecture() {
+                Some(name) => name.to_string(),
+                None => format!("{:#x}", arch.cputype()),
+            });
+            let member = context.member.map(|member| member.name);
+            let occurrence = occurrences
+                .entry((architecture.clone(), member.clone()))
+                .or_insert(0);
+            *occurrence += 1;
+            ((architecture, member, *occurrence - 1), symbols)
+        })
+        .collect())
+}
+
+/// Compare the symbols of two objects.
+///
+/// Only symbols that are global or weak in at least one of the objects are compared.
+fn diff_symbols(
+    old: &BTreeMap<String, SymbolInfo>,
+    new: &BTreeMap<String, SymbolInfo>,
+) -> Vec<SymbolDiff> {
+    let external = |symbol: &SymbolInfo| symbol.binding != Binding::Local;
+    let mut names: Vec<_> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| match (old.get(name), new.get(name)) {
+            (Some(old), Some(new)) if old != new && (external(old) || external(new)) => {
+                Some(SymbolDiff::Changed {
+                    old: old.clone(),
+                    new: new.clone(),
+                })
+            }
+            (Some(old), None) if external(old) => Some(SymbolDiff::Removed(old.clone())),
+            (None, Some(new)) if external(new) => Some(SymbolDiff::Added(new.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare the symbols exported by two binaries or archives of binaries.
+///
+/// Symbols are exported if they are defined, and global or weak.  Hidden symbols are exported
+/// from objects and archives, so changes in visibility are reported as changes.  Only objects
+/// with differences are returned, with the objects of `old` first.
+pub fn diff(old: &[u8], new: &[u8], options: &TransformOptions) -> Result<Vec<ObjectDiff>> {
+    let old = defined_symbols(old, options)?;
+    let mut new = defined_symbols(new, options)?;
+    let empty = BTreeMap::new();
+    let mut diffs = Vec::new();
+    let mut push = |key: ObjectKey, symbols: Vec<SymbolDiff>| {
+        if !symbols.is_empty() {
+            diffs.push(ObjectDiff {
+                architecture: key.0,
+                member: key.1,
+                symbols,
+            });
+        }
+    };
+    for (key, old_symbols) in old {
+        let new_symbols = match new.iter().position(|(new_key, _)| *new_key == key) {
+            Some(index) => new.remove(index).1,
+            None => BTreeMap::new(),
+        };
+        push(key, diff_symbols(&old_symbols, &new_symbols));
+    }
+    for (key, new_symbols) in new {
+        push(key, diff_symbols(&empty, &new_symbols));
+    }
+    Ok(diffs)
+}
@@ -0,0 +1,377 @@
+//! Locate names in DWARF debug information.
+//!
+//! Debuggers identify functions and variables by the names recorded in the debug information,
+//! which are independent of the symbol table.  Names are usually stored in string pools (the
+//! `.debug_str` and `.debug_line_str` sections) and referenced by offset, but short names may
+//! also be stored inline in `.debug_info`.
+//!
+//! Strings in the string pools are shared by every reference to them, so renaming a pool string
+//! renames every use of that name, not only the function or variable with that symbol.  Linkers
+//! may also merge strings that share a suffix, so a reference may point into the middle of
+//! another string.
+//!
+//! References may also be indices into `.debug_str_offsets`.  In relocatable ELF objects, the
+//! offsets stored in `.debug_info` and `.debug_str_offsets` are only complete once their
+//! relocations are applied.
+
+use crate::error::{Error, Result};
+use crate::patch::{Location, Rooted};
+use goblin::container::Ctx;
+use goblin::elf::header::{EM_AARCH64, EM_X86_64};
+use goblin::elf::reloc::{
+    R_AARCH64_ABS32, R_AARCH64_ABS64, R_X86_64_32, R_X86_64_64, R_X86_64_DTPOFF32,
+    R_X86_64_DTPOFF64,
+};
+use goblin::elf::section_header::SHF_COMPRESSED;
+use goblin::elf::Elf;
+use goblin::mach::MachO;
+use scroll::{Endian, Pwrite};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// Offsets into the string pools referenced by attributes in `.debug_info`.
+#[derive(Default)]
+struct References {
+    debug_str: BTreeSet<usize>,
+    debug_line_str: BTreeSet<usize>,
+}
+
+/// Read every string in a string pool.
+///
+/// Strings referenced by an offset in `references` that points into the middle of another string
+/// are also returned, so that renaming either string is detected as renaming both.
+fn pool_strings<'a>(
+    bytes: &'a [u8],
+    range: Range<usize>,
+    ctx: Ctx,
+    references: &BTreeSet<usize>,
+    names: &mut Vec<Rooted<&'a str>>,
+) -> Result<()> {
+    let pool = bytes
+        .get(range.clone())
+        .ok_or_else(|| Error::Malformed("DWARF string section out of bounds".to_string()))?;
+    let mut push = |offset: usize| {
+        let len = pool[offset..]
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(pool.len() - offset);
+        // Strings that are not valid UTF-8 cannot match a symbol name
+        if let (true, Ok(name)) = (len != 0, std::str::from_utf8(&pool[offset..offset + len])) {
+            let location = Location {
+                offset: range.start + offset,
+                size: len,
+                ctx,
+            };
+            names.push(Rooted::new(location, name));
+        }
+        len
+    };
+    let mut offset = 0;
+    while offset < pool.len() {
+        offset += push(offset) + 1;
+    }
+    for offset in references.range(1..pool.len()) {
+        if pool[offset - 1] != 0 {
+            push(*offset);
+        }
+    }
+    Ok(())
+}
+
+/// A relocation of a debug information section, as an offset into the section, the size of the
+/// relocated field, and its value.
+struct Relocation {
+    offset: usize,
+    size: usize,
+    value: u64,
+}
+
+/// The contents of a section, with its relocations applied.
+fn relocated<'a>(
+    bytes: &'a [u8],
+    range: Range<usize>,
+    relocations: &[Relocation],
+    ctx: Ctx,
+) -> Result<Cow<'a, [u8]>> {
+    let section = bytes
+        .get(range)
+        .ok_or_else(|| Error::Malformed("DWARF section out of bounds".to_string()))?;
+    if relocations.is_empty() {
+        return Ok(Cow::Borrowed(section));
+    }
+    let mut section = section.to_vec();
+    for relocation in relocations {
+        let field = relocation
+            .offset
+            .checked_add(relocation.size)
+            .and_then(|end| section.get_mut(relocation.offset..end))
+            .ok_or_else(|| Error::Malformed("DWARF relocation out of bounds".to_string()))?;
+        if relocation.size == 8 {
+            field.pwrite_with(relocation.value, 0, ctx.le)?;
+        } else {
+            field.pwrite_with(relocation.value as u32, 0, ctx.le)?;
+        }
+    }
+    Ok(Cow::Owned(section))
+}
+
+/// The sections of an object containing debug information names.
+#[derive(Default)]
+struct Sections {
+    debug_info: Option<Range<usize>>,
+    debug_abbrev: Option<Range<usize>>,
+    debug_str: Option<Range<usize>>,
+    debug_line_str: Option<Range<usize>>,
+    debug_str_offsets: Option<Range<usize>>,
+
+    /// The relocations of `.debug_info`
+    info_relocations: Vec<Relocation>,
+
+    /// The relocations of `.debug_str_offsets`
+    str_offsets_relocations: Vec<Relocation>,
+}
+
+impl Sections {
+    /// Read every name and linkage name of a function or variable stored inline in `.debug_info`,
+    /// and every reference to a string pool.
+    fn inline_names<'a>(
+        &self,
+        bytes: &'a [u8],
+        ctx: Ctx,
+        names: &mut Vec<Rooted<&'a str>>,
+        references: &mut References,
+    ) -> Result<()> {
+        use gimli::constants::*;
+
+        let (info_range, abbrev_range) = match (&self.debug_info, &self.debug_abbrev) {
+            (Some(info), Some(abbrev)) => (info.clone(), abbrev.clone()),
+            _ => return Ok(()),
+        };
+        let info = relocated(bytes, info_range.clone(), &self.info_relocations, ctx)?;
+        let abbrev = relocated(bytes, abbrev_range, &[], ctx)?;
+        let str_offsets = match &self.debug_str_offsets {
+            Some(range) => relocated(bytes, range.clone(), &self.str_offsets_relocations, ctx)?,
+            None => Cow::Borrowed(&[][..]),
+        };
+
+        let endian = match ctx.le {
+            Endian::Little => gimli::RunTimeEndian::Little,
+            Endian::Big => gimli::RunTimeEndian::Big,
+        };
+        let debug_info = gimli::DebugInfo::new(&info, endian);
+        let debug_abbrev = gimli::DebugAbbrev::new(&abbrev, endian);
+        let debug_str_offsets =
+            gimli::DebugStrOffsets::from(gimli::EndianSlice::new(&str_offsets, endian));
+        let dwarf_error = |e: gimli::Error| Error::Malformed(format!("DWARF: {}", e));
+
+        let mut units = debug_info.units();
+        while let Some(unit) = units.next().map_err(dwarf_error)? {
+            let abbreviations = unit.abbreviations(&debug_abbrev).map_err(dwarf_error)?;
+
+            // String indices are relative to the base given by the unit entry, which may follow them
+            let mut str_offsets_base = gimli::DebugStrOffsetsBase::default_for_encoding_and_file(
+                unit.encoding(),
+                gimli::DwarfFileType::Main,
+            );
+            if let Some((_, entry)) = unit
+                .entries(&abbreviations)
+                .next_dfs()
+                .map_err(dwarf_error)?
+            {
+                if let Some(gimli::AttributeValue::DebugStrOffsetsBase(base)) = entry
+                    .attr_value(DW_AT_str_offsets_base)
+                    .map_err(dwarf_error)?
+                {
+                    str_offsets_base = base;
+                }
+            }
+
+            let mut entries = unit.entries(&abbreviations);
+            while let Some((_, entry)) = entries.next_dfs().map_err(dwarf_error)? {
+                let named = entry.tag() == DW_TAG_subprogram || entry.tag() == DW_TAG_variable;
+                let mut attrs = entry.attrs();
+                while let Some(attr) = attrs.next().map_err(dwarf_error)? {
+                    match attr.raw_value() {
+                        gimli::AttributeValue::DebugStrRef(offset) => {
+                            references.debug_str.insert(offset.0);
+                        }
+                        gimli::AttributeValue::DebugStrOffsetsIndex(index) => {
+                            let offset = debug_str_offsets
+                                .get_str_offset(unit.format(), str_offsets_base, index)
+                                .map_err(dwarf_error)?;
+                            references.debug_str.insert(offset.0);
+                        }
+                        gimli::AttributeValue::DebugLineStrRef(offset) => {
+                            references.debug_line_str.insert(offset.0);
+                        }
+                        _ => (),
+                    }
+                    if !named
+                        || (attr.name() != DW_AT_name
+                            && attr.name() != DW_AT_linkage_name
+                            && attr.name() != DW_AT_MIPS_linkage_name)
+                    {
+                        continue;
+                    }
+                    if let gimli::AttributeValue::String(name) = attr.raw_value() {
+                        // Inline strings are never relocated, so they are read from the binary
+                        let offset = info_range.start
+                            + (name.slice().as_ptr() as usize - info.as_ptr() as usize);
+                        if let Ok(value) = std::str::from_utf8(&bytes[offset..offset + name.len()])
+                        {
+                            let location = Location {
+                                offset,
+                                size: value.len(),
+                                ctx,
+                            };
+                            names.push(Rooted::new(location, value));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn names(self, bytes: &[u8], ctx: Ctx) -> Result<Vec<Rooted<&str>>> {
+        let mut names = Vec::new();
+        let mut references = References::default();
+        self.inline_names(bytes, ctx, &mut names, &mut references)?;
+        if let Some(range) = self.debug_str {
+            pool_strings(bytes, range, ctx, &references.debug_str, &mut names)?;
+        }
+        if let Some(range) = self.debug_line_str {
+            pool_strings(bytes, range, ctx, &references.debug_line_str, &mut names)?;
+        }
+        Ok(names)
+    }
+}
+
+/// Find the names in an ELF binary's DWARF debug information.
+///
+/// Returns every string in the `.debug_str` and `.debug_line_str` string pools, and every name of
+/// a function or variable stored inline in `.debug_info`.  Compressed debug information can't be
+/// renamed, and returns an error, as do relocations of `.debug_info` and `.debug_str_offsets` with
+/// types other than absolute addresses on x86-64 and AArch64.
+pub fn names_from_elf<'a>(bytes: &'a [u8], elf: &Elf) -> Result<Vec<Rooted<&'a str>>> {
+    elf_names(bytes, elf, false)
+}
+
+/// Find the names in an ELF binary's DWARF debug information, ignoring compressed sections.
+pub(crate) fn uncompressed_names_from_elf<'a>(
+    bytes: &'a [u8],
+    elf: &Elf,
+) -> Result<Vec<Rooted<&'a str>>> {
+    elf_names(bytes, elf, true)
+}
+
+fn elf_names<'a>(
+    bytes: &'a [u8],
+    elf: &Elf,
+    skip_compressed: bool,
+) -> Result<Vec<Rooted<&'a str>>> {
+    let mut sections = Sections::default();
+    let mut indices = (None, None);
+    for (index, header) in elf.section_headers.iter().enumerate() {
+        let name = match elf.shdr_strtab.get_at(header.sh_name) {
+            Some(name) => name,
+            None => continue,
+        };
+        let section = match name {
+            ".debug_info" => {
+                indices.0 = Some(index);
+                &mut sections.debug_info
+            }
+            ".debug_abbrev" => &mut sections.debug_abbrev,
+            ".debug_str" => &mut sections.debug_str,
+            ".debug_line_str" => &mut sections.debug_line_str,
+            ".debug_str_offsets" => {
+                indices.1 = Some(index);
+                &mut sections.debug_str_offsets
+            }
+            _ => continue,
+        };
+        if header.sh_flags & u64::from(SHF_COMPRESSED) != 0 {
+            if skip_compressed {
+                continue;
+            }
+            return Err(Error::Unsupported(format!(
+                "renaming strings in compressed section {}",
+                name
+            )));
+        }
+        *section = Some(header.file_range().unwrap_or(0..0));
+    }
+
+    // The offsets of relocatable objects are stored as relocation addends rather than in place.
+    // Implicit addends (SHT_REL) are already stored in place.
+    for (index, relocations) in &elf.shdr_relocs {
+        let target = Some(elf.section_headers[*index].sh_info as usize);
+        let (section, relocated) = if target == indices.0 && sections.debug_info.is_some() {
+            (".debug_info", &mut sections.info_relocations)
+        } else if target == indices.1 && sections.debug_str_offsets.is_some() {
+            (".debug_str_offsets", &mut sections.str_offsets_relocations)
+        } else {
+            continue;
+        };
+        for relocation in relocations.iter() {
+            let addend = match relocation.r_addend {
+                Some(addend) => addend,
+                None => continue,
+            };
+            // Offsets of thread-local variables only appear in location expressions, which
+            // aren't read, so any value will do
+            let size = match (elf.header.e_machine, relocation.r_type) {
+                (EM_X86_64, R_X86_64_64)
+                | (EM_X86_64, R_X86_64_DTPOFF64)
+                | (EM_AARCH64, R_AARCH64_ABS64) => 8,
+                (EM_X86_64, R_X86_64_32)
+                | (EM_X86_64, R_X86_64_DTPOFF32)
+                | (EM_AARCH64, R_AARCH64_ABS32) => 4,
+                _ => {
+                    return Err(Error::Unsupported(format!(
+                        "relocation type {} in {}",
+                        relocation.r_type, section
+                    )))
+                }
+            };
+            let symbol = elf.syms.get(relocation.r_sym).ok_or_else(|| {
+                Error::Malformed(format!(
+                    "relocation in {} refers to a missing symbol",
+                    section
+                ))
+            })?;
+            relocated.push(Relocation {
+                offset: relocation.r_offset as usize,
+                size,
+                value: symbol.st_value.wrapping_add(addend as u64),
+            });
+        }
+    }
+    sections.names(bytes, crate::elf::context_from_elf(elf))
+}
+
+/// Find the names in a Mach-O binary's DWARF debug information.
+///
+/// Returns every string in the `__debug_str` and `__debug_line_str` string pools, and every name
+/// of a function or variable stored inline in `__debug_info`.  Note that, unlike symbol names,
+/// these names are not prefixed with an underscore.
+pub fn names_from_mach<'a>(bytes: &'a [u8], mach: &MachO) -> Result<Vec<Rooted<&'a str>>> {
+    let mut sections = Sections::default();
+    for (_, section) in crate::mach::section_headers(bytes, mach)? {
+        if section.segname()? != "__DWARF" {
+            continue;
+        }
+        let range = section.offset as usize..section.offset as usize + section.size as usize;
+        match section.name()? {
+            "__debug_info" => sections.debug_info = Some(range),
+            "__debug_abbrev" => sections.debug_abbrev = Some(range),
+            "__debug_str" => sections.debug_str = Some(range),
+            "__debug_line_str" => sections.debug_line_str = Some(range),
+            "__debug_str_offs" => sections.debug_str_offsets = Some(range),
+            _ => (),
+        }
+    }
+    sections.names(bytes, crate::mach::context_from_macho(mach))
+}
@@ -0,0 +1,1217 @@
+//! A high-level interface for editing binaries.
+//!
+//! ```no_run
+//! use symtool_backend::editor::Editor;
+//!
+//! # fn main() -> symtool_backend::error::Result<()> {
+//! Editor::open("libfoo.a")?
+//!     .hide("^foo_internal_")
+//!     .rename("foo_init", "bar_init")
+//!     .write("libbar.a")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::archive::Archive;
+use crate::elf::dynamic::{Dynamic, DynamicString};
+use crate::elf::version::{set_string_offset, Versions, VERSYM_HIDDEN, VER_FLG_BASE};
+use crate::error::{Error, Result};
+use crate::object::{Context, Object, TransformOptions};
+use crate::patch::{Patch, Rooted};
+use crate::report::{Change, ObjectReport, Report, Rule};
+use crate::strip::Action;
+use crate::symbol::{Binding, Kind, Symbol, Visibility};
+use crate::verify::VerifyOptions;
+use goblin::elf::dynamic::{DT_NEEDED, DT_SONAME};
+use goblin::elf::section_header::{SHN_ABS, SHT_DYNSYM, SHT_SYMTAB};
+use memmap2::{Mmap, MmapOptions};
+use regex::RegexSet;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::{Deref, Range};
+use std::path::{Path, PathBuf};
+
+/// Edits a binary or an archive of binaries.
+///
+/// Edits are collected with the builder methods, and applied all at once by
+/// [`into_bytes`](Editor::into_bytes) or [`write`](Editor::write).  Symbols are stripped before
+/// any other edit is applied.
+pub struct Editor {
+    source: Source,
+    mmap: bool,
+    hidden: Vec<String>,
+    default: Vec<String>,
+    strip: Vec<String>,
+    strip_locals: bool,
+    members: Vec<String>,
+    exclude_members: Vec<String>,
+    renames: HashMap<String, String>,
+    section_renames: HashMap<String, String>,
+    segment_renames: HashMap<String, String>,
+    version_renames: HashMap<String, String>,
+    symbol_versions: HashMap<String, String>,
+    soname: Option<String>,
+    needed_renames: HashMap<String, String>,
+    rename_dwarf: bool,
+    skip_unknown: bool,
+    require_match: bool,
+    deterministic: bool,
+    verify: bool,
+    verbose: bool,
+}
+
+/// The binary to edit.
+enum Source {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+/// The compiled patterns used while editing.
+struct Patterns {
+    hidden: Option<RegexSet>,
+    default: Option<RegexSet>,
+    strip: Option<RegexSet>,
+}
+
+pub(crate) fn regex_set(patterns: &[String]) -> Result<Option<RegexSet>> {
+    if patterns.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(RegexSet::new(patterns)?))
+    }
+}
+
+pub(crate) fn is_match(regex: &Option<RegexSet>, name: &str) -> bool {
+    regex.as_ref().is_some_and(|regex| regex.is_match(name))
+}
+
+/// The changes made to an object, and the number of times each rule matched.
+#[derive(Default)]
+struct Log {
+    changes: Vec<Change>,
+    matches: HashMap<Rule, usize>,
+
+    /// The ranges of the binary modified by patches
+    patched: Vec<Range<usize>>,
+}
+
+impl Log {
+    fn matched(&mut self, rule: Rule) {
+        *self.matches.entry(rule).or_default() += 1;
+    }
+
+    /// Count a match of each pattern in `set` that matches `name`.
+    fn matched_patterns(
+        &mut self,
+        set: &Option<RegexSet>,
+        patterns: &[String],
+        name: &str,
+        rule: fn(String) -> Rule,
+    ) {
+        if let Some(set) = set {
+            for index in set.matches(name).iter() {
+                self.matched(rule(patterns[index].clone()));
+            }
+        }
+    }
+
+    fn append(&mut self, other: Log) {
+        self.changes.extend(other.changes);
+        self.patched.extend(other.patched);
+        for (rule, matches) in other.matches {
+            *self.matches.entry(rule).or_default() += matches;
+        }
+    }
+}
+
+impl Editor {
+    /// Open a binary or archive to edit.
+    ///
+    /// The file is not read until the edits are applied.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        std::fs::metadata(path)?;
+        Ok(Self::new(Source::File(path.to_path_buf())))
+    }
+
+    /// Edit a binary or archive in memory.
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self::new(Source::Bytes(data))
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            source,
+            mmap: false,
+            hidden: Vec::new(),
+            default: Vec::new(),
+            strip: Vec::new(),
+            strip_locals: false,
+            members: Vec::new(),
+            exclude_members: Vec::new(),
+            renames: HashMap::new(),
+            section_renames: HashMap::new(),
+            segment_renames: HashMap::new(),
+            version_renames: HashMap::new(),
+            symbol_versions: HashMap::new(),
+            soname: None,
+            needed_renames: HashMap::new(),
+            rename_dwarf: false,
+            skip_unknown: false,
+            require_match: false,
+            deterministic: false,
+            verify: false,
+            verbose: false,
+        }
+    }
+
+    /// Set symbols with names matching the regex `pattern` to hidden visibility.
+    pub fn hide(mut self, pattern: &str) -> Self {
+        self.hidden.push(pattern.to_string());
+        self
+    }
+
+    /// Set symbols with names matching the regex `pattern` to default visibility.
+    ///
+    /// Takes precedence over [`hide`](Editor::hide) when both patterns match a symbol.
+    pub fn expose(mut self, pattern: &str) -> Self {
+        self.default.push(pattern.to_string());
+        self
+    }
+
+    /// Rename the symbol `old` to `new`, which must not be longer than `old`.
+    pub fn rename(mut self, old: &str, new: &str) -> Self {
+        self.renames.insert(old.to_string(), new.to_string());
+        self
+    }
+
+    /// Rename the section `old` to `new`.
+    ///
+    /// Mach-O sections may be qualified with their segment name, as in `SEGMENT,SECTION`, in
+    /// which case `new` must also be qualified.
+    pub fn rename_section(mut self, old: &str, new: &str) -> Self {
+        self.section_renames
+            .insert(old.to_string(), new.to_string());
+        self
+    }
+
+    /// Rename the Mach-O segment `old` to `new`.
+    ///
+    /// The segment names of the sections in the segment are also renamed.  Sections renamed with
+    /// a qualified name in the segment must be renamed to the new segment.
+    pub fn rename_segment(mut self, old: &str, new: &str) -> Self {
+        self.segment_renames
+            .insert(old.to_string(), new.to_string());
+        self
+    }
+
+    /// Rename the ELF symbol version `old` to `new`, which must not be longer than `old`.
+    ///
+    /// Both version definitions and versions required from dependencies are renamed, along with
+    /// the absolute symbols the linker defines for each version and versioned symbol names such
+    /// as `foo@old` and `foo@@old`.
+    pub fn rename_version(mut self, old: &str, new: &str) -> Self {
+        self.version_renames
+            .insert(old.to_string(), new.to_string());
+        self
+    }
+
+    /// Set the version of the ELF dynamic symbol `symbol` to `version`.
+    ///
+    /// The version must be defined by the binary or required from one of its dependencies, and is
+    /// identified by its name before any [`rename_version`](Editor::rename_version).  Whether
+    /// the symbol is the default version of its name is unchanged.
+    pub fn set_symbol_version(mut self, symbol: &str, version: &str) -> Self {
+        self.symbol_versions
+            .insert(symbol.to_string(), version.to_string());
+        self
+    }
+
+    /// Set the name of an ELF shared object (`DT_SONAME`) to `soname`, like
+    /// `patchelf --set-soname`.
+    ///
+    /// The shared object must already have a name.  See [`replace_needed`](Editor::replace_needed)
+    /// for names that don't fit in place.
+    pub fn set_soname(mut self, soname: &str) -> Self {
+        self.soname = Some(soname.to_string());
+        self
+    }
+
+    /// Replace the ELF dependency `old` (`DT_NEEDED`) with `new`, like
+    /// `patchelf --replace-needed`.
+    ///
+    /// The file names of the versions required from the dependency are also replaced.  Names
+    /// are replaced in place if they fit.  Otherwise, the dynamic string table is grown by
+    /// moving it to a new segment at the end of the file.  Archives containing shared objects
+    /// that grow are rewritten with [`Archive::write`](crate::archive::Archive::write).
+    pub fn replace_needed(mut self, old: &str, new: &str) -> Self {
+        self.needed_renames.insert(old.to_string(), new.to_string());
+        self
+    }
+
+    /// Also rename symbols in DWARF debug information.
+    pub fn rename_dwarf(mut self, rename_dwarf: bool) -> Self {
+        self.rename_dwarf = rename_dwarf;
+        self
+    }
+
+    /// Remove symbols with names matching the regex `pattern`.
+    ///
+    /// Fails if a matching symbol is referenced by a relocation, or if a matching global symbol
+    /// is defined by an archive member, since the archive's symbol table would still list it.
+    pub fn strip(mut self, pattern: &str) -> Self {
+        self.strip.push(pattern.to_string());
+        self
+    }
+
+    /// Remove local symbols not referenced by relocations.
+    pub fn strip_locals(mut self, strip_locals: bool) -> Self {
+        self.strip_locals = strip_locals;
+        self
+    }
+
+    /// Only edit archive members with names matching the regex `pattern`.
+    ///
+    /// If called more than once, members matching any of the patterns are edited.  Standalone
+    /// objects are always edited.
+    pub fn member(mut self, pattern: &str) -> Self {
+        self.members.push(pattern.to_string());
+        self
+    }
+
+    /// Leave archive members with names matching the regex `pattern` unmodified.
+    ///
+    /// Takes precedence over [`member`](Editor::member).
+    pub fn exclude_member(mut self, pattern: &str) -> Self {
+        self.exclude_members.push(pattern.to_string());
+        self
+    }
+
+    /// Leave archive members that are not ELF or Mach-O objects unmodified, rather than failing.
+    pub fn skip_unknown(mut self, skip_unknown: bool) -> Self {
+        self.skip_unknown = skip_unknown;
+        self
+    }
+
+    /// Fail if any pattern or rename does not match a name.
+    ///
+    /// Otherwise, unmatched rules are listed by [`Report::unmatched`].
+    pub fn require_match(mut self, require_match: bool) -> Self {
+        self.require_match = require_match;
+        self
+    }
+
+    /// Zero the timestamps, user IDs, and group IDs of archive members and set their modes to 644,
+    /// like `ar D`.
+    ///
+    /// See [`deterministic_headers`](crate::archive::deterministic_headers).
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Verify the edited binary against the original with
+    /// [`verify_with`](crate::verify::verify_with).
+    ///
+    /// Stripped objects are verified separately with
+    /// [`verify_strip`](crate::verify::verify_strip).  Growing the dynamic string table for
+    /// [`set_soname`](Editor::set_soname) or [`replace_needed`](Editor::replace_needed) is not
+    /// verified.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Edit a private, copy-on-write memory mapping of the file, rather than reading it into
+    /// memory.
+    ///
+    /// When writing, only the pages that are read are loaded and only the pages that are patched
+    /// are copied, which greatly reduces memory use for large archives.  The output is written by
+    /// copying the input file and then writing the modified ranges.  The input and output must be
+    /// different files, and the input may not be modified by another process while editing.
+    /// Verification reads the entire file, and binaries whose dynamic string table grows are
+    /// edited in memory.  Has no effect on editors created with [`from_bytes`](Editor::from_bytes),
+    /// or when using [`into_bytes`](Editor::into_bytes).
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Print each operation performed to standard output.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Apply the edits, returning the edited binary.
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        self.into_bytes_with_report().map(|(data, _)| data)
+    }
+
+    /// Apply the edits, returning the edited binary and a report of the changes.
+    pub fn into_bytes_with_report(mut self) -> Result<(Vec<u8>, Report)> {
+        let mut data = match std::mem::replace(&mut self.source, Source::Bytes(Vec::new())) {
+            Source::Bytes(data) => data,
+            Source::File(path) => std::fs::read(path)?,
+        };
+        if let Some(grown) = self.grow(&data)? {
+            data = grown;
+        }
+        let (report, _) = self.apply(&mut data, None)?;
+        Ok((data, report))
+    }
+
+    /// Apply the edits and write the edited binary to `path`, returning a report of the changes.
+    ///
+    /// Nothing is written if any edit fails.
+    pub fn write<P: AsRef<Path>>(self, path: P) -> Result<Report> {
+        if let (true, Source::File(input)) = (self.mmap, &self.source) {
+            if let (Ok(input), Ok(output)) = (input.canonicalize(), path.as_ref().canonicalize()) {
+                if input == output {
+                    return Err(Error::InvalidArgument(
+                        "Memory-mapped editing requires different input and output files"
+                            .to_string(),
+                    ));
+                }
+            }
+
+            return self.write_mapped(input, path.as_ref());
+        }
+        let (data, report) = self.into_bytes_with_report()?;
+        std::fs::write(path, data)?;
+        Ok(report)
+    }
+
+    fn write_mapped(&self, input: &Path, output: &Path) -> Result<Report> {
+        let file = std::fs::File::open(input)?;
+
+        // Safety: the input may not be modified by another process while mapped, which is
+        // documented on `Editor::mmap`
+        let original = unsafe { Mmap::map(&file)? };
+
+        // Growing the dynamic string table extends the file, which can't be done to a mapping, so
+        // the binary is edited in memory instead
+        if let Some(mut data) = self.grow(&original)? {
+            let (report, _) = self.apply(&mut data, None)?;
+            std::fs::write(output, data)?;
+            return Ok(report);
+        }
+
+        // Patches are applied to a private mapping, so only the pages they modify are copied.  The
+        // output starts as a copy of the input, and only the modified ranges are written to it.
+        // Safety: as above, and writes to the mapping are never written back to the input
+        let mut data = unsafe { MmapOptions::new().map_copy(&file)? };
+        let (report, patched) = self.apply(&mut data, Some(&original))?;
+
+        // The output is only touched once every patch has been applied and verified, but once it
+        // has been created or truncated, don't leave a partially written file behind
+        let result = Self::write_ranges(input, output, &data, patched);
+        if result.is_err() {
+            let _ = std::fs::remove_file(output);
+        }
+        result.map(|()| report)
+    }
+
+    /// Copy `input` to `output`, and overwrite the `ranges` of the copy with those of `data`.
+    fn write_ranges(
+        input: &Path,
+        output: &Path,
+        data: &[u8],
+        ranges: Vec<Range<usize>>,
+    ) -> Result<()> {
+        std::fs::copy(input, output)?;
+        let mut output = std::fs::OpenOptions::new().write(true).open(output)?;
+        for range in crate::verify::merge_ranges(ranges) {
+            output.seek(SeekFrom::Start(range.start as u64))?;
+            output.write_all(&data[range])?;
+        }
+        Ok(())
+    }
+
+    fn transform_options(&self) -> Result<TransformOptions> {
+        Ok(TransformOptions {
+            skip_unknown: self.skip_unknown,
+            members: regex_set(&self.members)?,
+            exclude_members: regex_set(&self.exclude_members)?,
+        })
+    }
+
+    /// The binary with its dynamic string table grown, if it is an ELF binary and a new name is
+    /// longer than the original and not already in the string table.
+    ///
+    /// The members of an archive are grown individually, and the archive is rewritten with the
+    /// resized members.
+    fn grow(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        if !crate::archive::is_archive(data) {
+            return self.grow_object(data);
+        }
+
+        // The members are only copied if one of them grows
+        let options = self.transform_options()?;
+        let mut grown = Vec::new();
+        for object in crate::object::object_ranges(data)? {
+            let name = match &object.context.member {
+                Some(member) if options.selects(&member.name) => member.name.clone(),
+                _ => continue,
+            };
+            let range = object.offset..object.offset + object.size;
+            match self.grow_object(&data[range]) {
+                Ok(Some(member)) => grown.push((name, member)),
+                Ok(None) => {}
+                Err(e) => return Err(Error::Nested(object.context, Box::new(e))),
+            }
+        }
+        if grown.is_empty() {
+            return Ok(None);
+        }
+
+        // Both list the members in the order they are stored, but may disagree on which members
+        // are symbol tables
+        let mut archive = Archive::parse(data)?;
+        let mut members = archive.members.iter_mut();
+        for (name, data) in grown {
+            if let Some(member) = members.by_ref().find(|member| member.name == name) {
+                member.data = data;
+            }
+        }
+        archive.write().map(Some)
+    }
+
+    fn grow_object(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let elf = match goblin::Object::parse(data) {
+            Ok(goblin::Object::Elf(elf)) => elf,
+            _ => return Ok(None),
+        };
+        let dynamic = match Dynamic::from_elf(data, &elf)? {
+            Some(dynamic) => dynamic,
+            None => return Ok(None),
+        };
+        let mut strings = Vec::new();
+        for string in &dynamic.strings {
+            if let Some(new_name) = self.dynamic_rename(string) {
+                if new_name.len() > string.name.value.len() && dynamic.find(new_name).is_none() {
+                    strings.push(new_name);
+                }
+            }
+        }
+        if strings.is_empty() {
+            return Ok(None);
+        }
+        let (size, patches) = crate::elf::dynamic::grow_strtab(data, &elf, &strings)?;
+        let mut grown = data.to_vec();
+        grown.resize(size, 0);
+        for patch in patches {
+            patch.apply(&mut grown);
+        }
+        Ok(Some(grown))
+    }
+
+    /// Apply the edits to `data`, returning a report and the ranges of `data` that were modified.
+    ///
+    /// `original` may provide an unmodified copy of `data` to verify against, if one is
+    /// available without copying.
+    fn apply(
+        &self,
+        data: &mut [u8],
+        original: Option<&[u8]>,
+    ) -> Result<(Report, Vec<Range<usize>>)> {
+        self.validate()?;
+        let patterns = Patterns {
+            hidden: regex_set(&self.hidden)?,
+            default: regex_set(&self.default)?,
+            strip: regex_set(&self.strip)?,
+        };
+        let options = self.transform_options()?;
+
+        // Stripping moves symbols, so it is performed separately, before any other edit
+        let strip = patterns.strip.is_some() || self.strip_locals;
+        let mut logs = if strip {
+            self.transform(data, &options, |bytes, object, context, log| {
+                self.strip_object(bytes, object, context, &patterns, log)
+            })?
+        } else {
+            Vec::new()
+        };
+
+        let original = match original {
+            _ if !self.verify => None,
+            Some(original) if !strip => Some(Cow::Borrowed(original)),
+            _ => Some(Cow::Owned(data.to_vec())),
+        };
+        let edits = self.transform(data, &options, |bytes, object, _, log| {
+            self.edit(bytes, object, &patterns, log)
+        })?;
+        let mut patched = Vec::new();
+        if self.deterministic {
+            for patch in crate::archive::deterministic_headers(data)? {
+                patch.apply(data);
+                patched.push(patch.range());
+            }
+        }
+
+        // Only the edits are verified against the patches that made them, since stripping is
+        // verified as each object is stripped
+        for (_, log) in edits.iter().flatten() {
+            patched.extend(log.patched.iter().cloned());
+        }
+        if let Some(original) = original {
+            let options = VerifyOptions {
+                rename_dwarf: self.rename_dwarf,
+                intended: Some(patched.clone()),
+            };
+            crate::verify::verify_with(&original, data, &options)?;
+        }
+
+        // Both passes transform the same objects
+        if logs.is_empty() {
+            logs = edits;
+        } else {
+            for (log, edit) in logs.iter_mut().zip(edits) {
+                if let (Some((_, log)), Some((_, edit))) = (log, edit) {
+                    log.append(edit);
+                }
+            }
+        }
+        if strip {
+            for (_, log) in logs.iter_mut().flatten() {
+                patched.append(&mut log.patched);
+            }
+        }
+        let report = self.report(logs);
+        if self.require_match {
+            let unmatched: Vec<_> = report.unmatched().cloned().collect();
+            if !unmatched.is_empty() {
+                return Err(Error::UnmatchedRules(unmatched));
+            }
+        }
+        Ok((report, patched))
+    }
+
+    /// Every rule, grouped by kind.  Renames are sorted by the original name.
+    fn rules(&self) -> Vec<Rule> {
+        fn sorted(renames: &HashMap<String, String>) -> Vec<String> {
+            let mut names: Vec<_> = renames.keys().cloned().collect();
+            names.sort();
+            names
+        }
+        let mut rules = Vec::new();
+        rules.extend(self.hidden.iter().cloned().map(Rule::Hide));
+        rules.extend(self.default.iter().cloned().map(Rule::Expose));
+        rules.extend(self.strip.iter().cloned().map(Rule::Strip));
+        rules.extend(sorted(&self.renames).into_iter().map(Rule::Rename));
+        rules.extend(
+            sorted(&self.section_renames)
+                .into_iter()
+                .map(Rule::RenameSection),
+        );
+        rules.extend(
+            sorted(&self.segment_renames)
+                .into_iter()
+                .map(Rule::RenameSegment),
+        );
+        rules.extend(
+            sorted(&self.version_renames)
+                .into_iter()
+                .map(Rule::RenameVersion),
+        );
+        rules.extend(
+            sorted(&self.symbol_versions)
+                .into_iter()
+                .map(Rule::SymbolVersion),
+        );
+        rules.extend(self.soname.iter().cloned().map(Rule::Soname));
+        rules.extend(
+            sorted(&self.needed_renames)
+                .into_iter()
+                .map(Rule::ReplaceNeeded),
+        );
+        rules
+    }
+
+    fn report(&self, logs: Vec<Option<(Context, Log)>>) -> Report {
+        let mut matches = HashMap::new();
+        let mut objects = Vec::new();
+        for (context, log) in logs.into_iter().flatten() {
+            for (rule, count) in log.matches {
+                *matches.entry(rule).or_default() += count;
+            }
+            objects.push(ObjectReport {
+                context,
+                changes: log.changes,
+            });
+        }
+        let rules = self
+            .rules()
+            .into_iter()
+            .map(|rule| {
+                let count = matches.get(&rule).copied().unwrap_or(0);
+                (rule, count)
+            })
+            .collect();
+        Report { objects, rules }
+    }
+
+    /// Apply a transformation to each object, returning the log for each object that was
+    /// transformed.
+    ///
+    /// The changes logged for each object are printed in order, if verbose.  Objects are
+    /// transformed in parallel when the `rayon` feature is enabled.
+    fn transform<F>(
+        &self,
+        data: &mut [u8],
+        options: &TransformOptions,
+        transformation: F,
+    ) -> Result<Vec<Option<(Context, Log)>>>
+    where
+        F: for<'a> Fn(&'a [u8], Object, &Context, &mut Log) -> Result<Vec<Patch>> + Sync,
+    {
+        let transformation = |bytes: &[u8], object: Object, context: &Context| {
+            let mut log = Log::default();
+            transformation(bytes, object, context, &mut log)
+                .map(|patches| (patches, (context.clone(), log)))
+        };
+        #[cfg(feature = "rayon")]
+        let results = crate::object::transform_each_parallel(data, options, &transformation);
+        #[cfg(not(feature = "rayon"))]
+        let results = crate::object::transform_each(data, options, &transformation);
+        let mut logs = Vec::new();
+        for result in results {
+            let log = result?.map(|((context, mut log), patched)| {
+                log.patched = patched;
+                (context, log)
+            });
+            if let (true, Some((_, log))) = (self.verbose, &log) {
+                for change in &log.changes {
+                    println!("{}", change);
+                }
+            }
+            logs.push(log);
+        }
+        Ok(logs)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (old, new) in &self.renames {
+            if new.len() > old.len() {
+                return Err(Error::InvalidArgument(format!("Replacement symbol names cannot have more characters than the original name. Symbol '{}' cannot be renamed to '{}'.", old, new)));
+            }
+        }
+        for (old, new) in &self.version_renames {
+            if new.len() > old.len() {
+                return Err(Error::InvalidArgument(format!("Replacement version names cannot have more characters than the original name. Version '{}' cannot be renamed to '{}'.", old, new)));
+            }
+        }
+        for (old, new) in &self.section_renames {
+            if old.contains(',') != new.contains(',') {
+                return Err(Error::InvalidArgument(format!("Section '{}' cannot be renamed to '{}'. Both names must be qualified with a segment name, or neither.", old, new)));
+            }
+
+            // The segment name of each section must match its segment command
+            if let (Some((segment, _)), Some((new_segment, _))) =
+                (old.split_once(','), new.split_once(','))
+            {
+                if let Some(renamed) = self.segment_renames.get(segment) {
+                    if renamed != new_segment {
+                        return Err(Error::InvalidArgument(format!("Section '{}' cannot be renamed to '{}' while segment '{}' is renamed to '{}'. The section must be renamed to '{},...'.", old, new, segment, renamed, renamed)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn strip_object(
+        &self,
+        bytes: &[u8],
+        object: Object,
+        context: &Context,
+        patterns: &Patterns,
+        log: &mut Log,
+    ) -> Result<Vec<Patch>> {
+        let stripped = crate::strip::strip(bytes, &object, |symbol| {
+            self.strip_action(symbol, patterns, log)
+        })?;
+
+        // The archive symbol table lists the global symbols of each member, and would still refer
+        // to removed globals
+        if context.member.is_some() {
+            if let Some((_, symbol)) = stripped
+                .removed
+                .iter()
+                .find(|(_, symbol)| symbol.is_defined() && symbol.binding() != Binding::Local)
+            {
+                return Err(Error::Unsupported(format!(
+                    "stripping global symbol \"{}\" from an archive member",
+                    symbol.name().map_or("", |name| name.value)
+                )));
+            }
+        }
+
+        for (index, symbol) in &stripped.removed {
+            let name = match symbol.name() {
+                Some(name) => name.value.to_string(),
+                None => format!("#{}", index),
+            };
+            log.changes.push(Change::StripSymbol { name });
+        }
+        if self.verify && !stripped.removed.is_empty() {
+            let mut modified = bytes.to_vec();
+            for patch in &stripped.patches {
+                patch.apply(&mut modified);
+            }
+            crate::verify::verify_strip(bytes, &modified, &stripped.removed)?;
+        }
+        Ok(stripped.patches)
+    }
+
+    fn strip_action(&self, symbol: &Symbol, patterns: &Patterns, log: &mut Log) -> Action {
+        match symbol.name().map(|name| name.value) {
+            Some(name) if symbol.kind() != Kind::Debug && is_match(&patterns.strip, name) => {
+                log.matched_patterns(&patterns.strip, &self.strip, name, Rule::Strip);
+                Action::Remove
+            }
+            _ if self.strip_locals && symbol.binding() == Binding::Local => {
+                Action::RemoveUnreferenced
+            }
+            _ => Action::Keep,
+        }
+    }
+
+    fn change_visibility(
+        &self,
+        symbol: &Symbol,
+        name: &str,
+        patterns: &Patterns,
+        log: &mut Log,
+    ) -> Result<Option<Patch>> {
+        log.matched_patterns(&patterns.default, &self.default, name, Rule::Expose);
+        log.matched_patterns(&patterns.hidden, &self.hidden, name, Rule::Hide);
+        let visibility = if is_match(&patterns.default, name) {
+            Visibility::Default
+        } else if is_match(&patterns.hidden, name) {
+            Visibility::Hidden
+        } else {
+            return Ok(None);
+        };
+        let patch = symbol.set_visibility(visibility)?;
+        if patch.is_some() {
+            log.changes.push(Change::SetVisibility {
+                name: name.to_string(),
+                old: symbol.visibility(),
+                new: visibility,
+            });
+        }
+        Ok(patch)
+    }
+
+    /// Rename Mach-O sections and segments, which are stored in fixed-length fields.
+    fn rename_mach_sections(
+        &self,
+        bytes: &[u8],
+        mach: &goblin::mach::MachO,
+        log: &mut Log,
+    ) -> Result<Vec<Patch>> {
+        let mut patches = Vec::new();
+        for (segname, sectname, _) in crate::mach::SectionIter::from_mach(bytes, mach)?
+            .collect::<Result<Vec<_>>>()?
+            .iter()
+        {
+            let qualified = format!("{},{}", segname.deref(), sectname.deref());
+            let mut new_segname = None;
+            let mut new_sectname = None;
+            if let Some(new_name) = self.section_renames.get(&qualified) {
+                // Checked by `validate`
+                let (segname, sectname) = new_name.split_once(',').unwrap();
+                new_segname = Some(segname);
+                new_sectname = Some(sectname);
+                log.matched(Rule::RenameSection(qualified.clone()));
+                log.changes.push(Change::RenameSection {
+                    old: qualified,
+                    new: new_name.clone(),
+                });
+            } else if let Some(new_name) = self.section_renames.get(*sectname.deref()) {
+                new_sectname = Some(new_name.as_str());
+                log.matched(Rule::RenameSection(sectname.to_string()));
+                log.changes.push(Change::RenameSection {
+                    old: sectname.to_string(),
+                    new: new_name.clone(),
+                });
+            }
+
+            // The segment name of each section must match its segment command, which is renamed
+            // below
+            if let Some(new_name) = self.segment_renames.get(*segname.deref()) {
+                log.matched(Rule::RenameSegment(segname.to_string()));
+                new_segname = Some(new_name.as_str());
+            }
+            if let Some(new_segname) = new_segname {
+                patches.push(crate::rename::rename_patch(segname, new_segname)?);
+            }
+            if let Some(new_sectname) = new_sectname {
+                patches.push(crate::rename::rename_patch(sectname, new_sectname)?);
+            }
+        }
+        for segname in crate::mach::SegmentIter::from_mach(bytes, mach)
+            .collect::<Result<Vec<_>>>()?
+            .iter()
+        {
+            if let Some(new_name) = self.segment_renames.get(*segname.deref()) {
+                log.matched(Rule::RenameSegment(segname.to_string()));
+                log.changes.push(Change::RenameSegment {
+                    old: segname.to_string(),
+                    new: new_name.clone(),
+                });
+                patches.push(crate::rename::rename_patch(segname, new_name)?);
+            }
+        }
+        Ok(patches)
+    }
+
+    /// The new name of a symbol that names a renamed ELF symbol version.
+    ///
+    /// These are the absolute symbols the linker defines for each version, and symbols with
+    /// versioned names such as `foo@VERSION` or `foo@@VERSION`.
+    fn version_symbol_rename(&self, symbol: &Symbol, name: &str) -> Option<String> {
+        let sym = match symbol {
+            Symbol::Elf { sym, .. } => sym,
+            Symbol::MachO { .. } => return None,
+        };
+        if sym.st_shndx as u32 == SHN_ABS {
+            if let Some(new_name) = self.version_renames.get(name) {
+                return Some(new_name.clone());
+            }
+        }
+        let (base, version) = name.rsplit_once('@')?;
+        self.version_renames
+            .get(version)
+            .map(|new_version| format!("{}@{}", base, new_version))
+    }
+
+    /// Assign versions to ELF dynamic symbols.
+    fn set_symbol_versions(
+        &self,
+        dynamic_symbols: &[Symbol],
+        versions: &Versions,
+        log: &mut Log,
+    ) -> Result<Vec<Patch>> {
+        let mut patches = Vec::new();
+        for (index, symbol) in dynamic_symbols.iter().enumerate() {
+            let name = match symbol.name() {
+                Some(name) => name.value,
+                None => continue,
+            };
+            let version = match self.symbol_versions.get(name) {
+                Some(version) => version,
+                None => continue,
+            };
+            log.matched(Rule::SymbolVersion(name.to_string()));
+            let version_index = versions.index(version).ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "Symbol '{}' cannot be assigned version '{}', which is not defined or required.",
+                    name, version
+                ))
+            })?;
+            let entry = versions.symbols.get(index).ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "Symbol '{}' cannot be assigned a version, because the binary has no symbol versions.",
+                    name
+                ))
+            })?;
+            if entry.value & !VERSYM_HIDDEN != version_index {
+                log.changes.push(Change::SetSymbolVersion {
+                    name: name.to_string(),
+                    old: versions.name(entry.value).map(str::to_string),
+                    new: version.clone(),
+                });
+                patches.push(crate::elf::version::set_version(entry, version_index)?);
+            }
+        }
+        Ok(patches)
+    }
+
+    /// Rename ELF symbol versions, returning each version name paired with its replacement.
+    fn rename_versions<'a>(
+        &'a self,
+        versions: &'a Versions,
+        log: &mut Log,
+        patches: &mut Vec<Patch>,
+    ) -> Result<Vec<(&'a Rooted<&'a str>, Option<&'a str>)>> {
+        let mut names = Vec::new();
+        let mut rename = |name: &'a Rooted<&'a str>, hash: &Rooted<u32>| -> Result<()> {
+            let new_name = self.version_renames.get(name.value).map(String::as_str);
+            if let Some(new_name) = new_name {
+                log.matched(Rule::RenameVersion(name.to_string()));
+                log.changes.push(Change::RenameVersion {
+                    old: name.to_string(),
+                    new: new_name.to_string(),
+                });
+                patches.push(crate::elf::version::rehash(hash, new_name)?);
+            }
+            names.push((name, new_name));
+            Ok(())
+        };
+        for definition in &versions.definitions {
+            // The base version is named after the shared object, and renamed with it
+            if definition.flags & VER_FLG_BASE != 0 && self.soname.is_some() {
+                continue;
+            }
+            rename(&definition.name, &definition.hash)?;
+        }
+        for version in versions
+            .requirements
+            .iter()
+            .flat_map(|requirement| &requirement.versions)
+        {
+            rename(&version.name, &version.hash)?;
+        }
+
+        // Parent versions aren't renamed by themselves, but share the string table
+        for definition in &versions.definitions {
+            for parent in &definition.parents {
+                names.push((
+                    parent,
+                    self.version_renames.get(parent.value).map(String::as_str),
+                ));
+            }
+        }
+        Ok(names)
+    }
+
+    /// The new name of the shared object or dependency named by a dynamic section entry, if any.
+    fn dynamic_rename(&self, string: &DynamicString) -> Option<&str> {
+        match string.entry.d_tag {
+            DT_SONAME => self.soname.as_deref(),
+            DT_NEEDED => self
+                .needed_renames
+                .get(string.name.value)
+                .map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Rename the ELF shared object and its dependencies, returning each affected string in the
+    /// dynamic string table paired with its replacement, if it is replaced in place.
+    ///
+    /// Names that don't fit in place are replaced by pointing to a copy of the new name elsewhere
+    /// in the string table, which [`grow`](Editor::grow) adds if necessary.
+    fn rename_dynamic<'a>(
+        &'a self,
+        dynamic: &'a Dynamic,
+        versions: &'a Versions,
+        log: &mut Log,
+        patches: &mut Vec<Patch>,
+    ) -> Result<Vec<(&'a Rooted<&'a str>, Option<&'a str>)>> {
+        let replace = |name: &'a Rooted<&'a str>,
+                       new_name: Option<&'a str>,
+                       set_offset: &dyn Fn(usize) -> Result<Patch>,
+                       patches: &mut Vec<Patch>|
+         -> Result<(&'a Rooted<&'a str>, Option<&'a str>)> {
+            match new_name {
+                Some(new_name) if new_name.len() > name.value.len() => {
+                    let offset = dynamic.find(new_name).ok_or_else(|| Error::ReplaceString {
+                        original: name.value.to_string(),
+                        replacement: new_name.to_string(),
+                    })?;
+                    patches.push(set_offset(offset)?);
+                    Ok((name, None))
+                }
+                _ => Ok((name, new_name)),
+            }
+        };
+
+        let mut names = Vec::new();
+        for string in &dynamic.strings {
+            let new_name = self.dynamic_rename(string);
+            if let Some(new_name) = new_name {
+                let old = string.name.to_string();
+                let (rule, change) = if string.entry.d_tag == DT_SONAME {
+                    let rule = Rule::Soname(new_name.to_string());
+                    let change = Change::SetSoname {
+                        old,
+                        new: new_name.to_string(),
+                    };
+                    (rule, change)
+                } else {
+                    let rule = Rule::ReplaceNeeded(old.clone());
+                    let change = Change::ReplaceNeeded {
+                        old,
+                        new: new_name.to_string(),
+                    };
+                    (rule, change)
+                };
+                log.matched(rule);
+                if new_name != string.name.value {
+                    log.changes.push(change);
+                }
+            }
+            names.push(replace(
+                &string.name,
+                new_name,
+                &|offset| string.set_offset(offset),
+                patches,
+            )?);
+        }
+
+        // The dynamic linker matches the versions required from a dependency by its file name, so
+        // it must match the new name
+        for requirement in &versions.requirements {
+            let new_name = self
+                .needed_renames
+                .get(requirement.file.value)
+                .map(String::as_str);
+            names.push(replace(
+                &requirement.file,
+                new_name,
+                &|offset| set_string_offset(&requirement.file_offset, offset),
+                patches,
+            )?);
+        }
+        if let (Some(soname), Some(_)) = (&self.soname, dynamic.soname()) {
+            for definition in &versions.definitions {
+                if definition.flags & VER_FLG_BASE != 0 {
+                    patches.push(crate::elf::version::rehash(&definition.hash, soname)?);
+                    names.push(replace(
+                        &definition.name,
+                        Some(soname),
+                        &|offset| set_string_offset(&definition.name_offset, offset),
+                        patches,
+                    )?);
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    fn edit(
+        &self,
+        bytes: &[u8],
+        object: Object,
+        patterns: &Patterns,
+        log: &mut Log,
+    ) -> Result<Vec<Patch>> {
+        let mut patches = Vec::new();
+        let symbols = crate::symbol::symbols(bytes, &object)?;
+        let mut section_names = Vec::new();
+        let mut versions = Versions::default();
+        let mut dynamic = None;
+        let mut dynamic_symbols = Vec::new();
+        let dwarf_names = match &object {
+            Object::Elf(elf) => {
+                // Section names are only needed to rename them, or to check that renamed symbols
+                // don't share storage with them
+                let shared_strtab = crate::elf::shstrndx(elf).is_some_and(|shstrndx| {
+                    elf.section_headers.iter().any(|header| {
+                        matches!(header.sh_type, SHT_SYMTAB | SHT_DYNSYM)
+                            && header.sh_link as usize == shstrndx
+                    })
+                });
+                if !self.section_renames.is_empty() || shared_strtab {
+                    for section in crate::elf::SectionIter::from_elf(bytes, elf)? {
+                        section_names.extend(section?.0);
+                    }
+                }
+                versions = Versions::from_elf(bytes, elf)?;
+                dynamic = Dynamic::from_elf(bytes, elf)?;
+                for symbol in crate::elf::SymtabIter::dynsym_from_elf(bytes, elf)?
+                    .into_iter()
+                    .flatten()
+                {
+                    let (name, sym) = symbol?;
+                    dynamic_symbols.push(Symbol::Elf { name, sym });
+                }
+                patches.extend(self.set_symbol_versions(&dynamic_symbols, &versions, log)?);
+                if self.rename_dwarf {
+                    crate::dwarf::names_from_elf(bytes, elf)?
+                } else {
+                    Vec::new()
+                }
+            }
+            Object::MachO(mach) => {
+                patches.extend(self.rename_mach_sections(bytes, mach, log)?);
+                if self.rename_dwarf {
+                    crate::dwarf::names_from_mach(bytes, mach)?
+                } else {
+                    Vec::new()
+                }
+            }
+        };
+
+        // Section, symbol, and debug information names may share a string table, so they are
+        // renamed together
+        let mut names = Vec::new();
+        for name in &section_names {
+            let new_name = self.section_renames.get(*name.deref()).map(String::as_str);
+            if let Some(new_name) = new_name {
+                log.matched(Rule::RenameSection(name.to_string()));
+                log.changes.push(Change::RenameSection {
+                    old: name.to_string(),
+                    new: new_name.to_string(),
+                });
+            }
+            names.push((name, new_name));
+        }
+        let version_symbol_names: Vec<_> = symbols
+            .iter()
+            .map(|symbol| {
+                let name = symbol.name()?;
+                self.version_symbol_rename(symbol, name.value)
+            })
+            .collect();
+        for (symbol, version_symbol_name) in symbols.iter().zip(&version_symbol_names) {
+            if let Some(name) = symbol.name() {
+                // Debugging entries that name a symbol are renamed with it, but others (such as
+                // file paths) are left alone
+                let new_name = if symbol.has_symbol_name() {
+                    self.renames.get(*name.deref()).map(String::as_str)
+                } else {
+                    None
+                };
+                let new_name = new_name.or(version_symbol_name.as_deref());
+                if let (Some(new_name), false) = (new_name, symbol.kind() == Kind::Debug) {
+                    log.matched(Rule::Rename(name.to_string()));
+                    log.changes.push(Change::RenameSymbol {
+                        old: name.to_string(),
+                        new: new_name.to_string(),
+                    });
+                }
+                names.push((name, new_name));
+                patches.extend(self.change_visibility(symbol, name, patterns, log)?);
+            }
+        }
+
+        // Dynamic symbol names are only renamed with the versions they name, but share the
+        // string table with the version names
+        let dynamic_symbol_names: Vec<_> = dynamic_symbols
+            .iter()
+            .map(|symbol| {
+                let name = symbol.name()?;
+                self.version_symbol_rename(symbol, name.value)
+            })
+            .collect();
+        for (symbol, new_name) in dynamic_symbols.iter().zip(&dynamic_symbol_names) {
+            if let Some(name) = symbol.name() {
+                names.push((name, new_name.as_deref()));
+            }
+        }
+        names.extend(self.rename_versions(&versions, log, &mut patches)?);
+        if let Some(dynamic) = &dynamic {
+            names.extend(self.rename_dynamic(dynamic, &versions, log, &mut patches)?);
+        }
+
+        // Names in Mach-O debug information are not prefixed with an underscore
+        let is_macho = matches!(object, Object::MachO(_));
+        for name in &dwarf_names {
+            let new_name = if is_macho {
+                self.renames
+                    .get(&format!("_{}", name.deref()))
+                    .map(|new_name| new_name.strip_prefix('_').unwrap_or(new_name))
+            } else {
+                self.renames.get(*name.deref()).map(String::as_str)
+            };
+            names.push((name, new_name));
+        }
+        patches.extend(crate::rename::rename_strings(bytes, &names)?);
+        Ok(patches)
+    }
+}
@@ -3,13 +3,16 @@
 use crate::error::{Error, Result};
 use crate::patch::{Location, Rooted};
 use goblin::container::{Container, Ctx, Endian};
-use goblin::elf::section_header::{SHT_DYNSYM, SHT_SYMTAB};
+use goblin::elf::section_header::{SHN_UNDEF, SHN_XINDEX, SHT_DYNSYM, SHT_SYMTAB};
 use goblin::elf::sym::Sym;
 use goblin::elf::{Elf, SectionHeader};
 use scroll::ctx::TryFromCtx;
 use scroll::Pread;
 
-fn context_from_elf(elf: &Elf) -> Ctx {
+pub mod dynamic;
+pub mod version;
+
+pub(crate) fn context_from_elf(elf: &Elf) -> Ctx {
     let container = if elf.is_64 {
         Container::Big
     } else {
@@ -61,11 +64,7 @@ impl<'a> SymtabIter<'a> {
             symoff: header.sh_offset as usize,
             stroff: headers[header.sh_link as usize].sh_offset as usize,
             step: header.sh_entsize as usize,
-            count: if header.sh_entsize == 0 {
-                0
-            } else {
-                header.sh_size / header.sh_entsize
-            } as usize,
+            count: header.sh_size.checked_div(header.sh_entsize).unwrap_or(0) as usize,
             index: 0,
         })
     }
@@ -129,7 +128,7 @@ impl<'a> std::iter::Iterator for SymtabIter<'a> {
                     Rooted::new(location, sym)
                 };
                 let name = if sym.st_name != 0 {
-                    let offset = self.stroff + sym.st_name as usize;
+                    let offset = self.stroff + sym.st_name;
                     let name: &str = self.bytes.pread(offset)?;
                     let location = Location {
                         offset,
@@ -145,3 +144,109 @@ impl<'a> std::iter::Iterator for SymtabIter<'a> {
         }
     }
 }
+
+/// The index of the section header string table, if any.
+///
+/// Objects with at least `SHN_LORESERVE` sections store the index in the `sh_link` field of the
+/// first section header, and set `e_shstrndx` to `SHN_XINDEX`.
+pub(crate) fn shstrndx(elf: &Elf) -> Option<usize> {
+    match elf.header.e_shstrndx as u32 {
+        SHN_UNDEF => None,
+        SHN_XINDEX => elf
+            .section_headers
+            .first()
+            .map(|header| header.sh_link as usize),
+        index => Some(index as usize),
+    }
+}
+
+/// An iterator over ELF section headers.
+pub struct SectionIter<'a> {
+    bytes: &'a [u8],
+    ctx: Ctx,
+    shoff: usize,
+    step: usize,
+    count: usize,
+    stroff: Option<usize>,
+    index: usize,
+}
+
+impl<'a> SectionIter<'a> {
+    /// Construct a `SectionIter` from an ELF binary's section header table.
+    ///
+    /// Section names are read from the section header string table, indicated by `e_shstrndx`.
+    pub fn from_elf(bytes: &'a [u8], elf: &Elf) -> Result<Self> {
+        let ctx = context_from_elf(elf);
+        if elf.section_headers.is_empty() {
+            return Ok(Self {
+                bytes,
+                ctx,
+                shoff: 0,
+                step: 0,
+                count: 0,
+                stroff: None,
+                index: 0,
+            });
+        }
+        if (elf.header.e_shentsize as usize) < SectionHeader::size(ctx) {
+            return Err(Error::Malformed("e_shentsize too small".to_string()));
+        }
+        let stroff = match shstrndx(elf) {
+            None => None,
+            Some(index) => Some(
+                elf.section_headers
+                    .get(index)
+                    .ok_or_else(|| Error::Malformed("e_shstrndx too large".to_string()))?
+                    .sh_offset as usize,
+            ),
+        };
+        Ok(Self {
+            bytes,
+            ctx,
+            shoff: elf.header.e_shoff as usize,
+            step: elf.header.e_shentsize as usize,
+            count: elf.section_headers.len(),
+            stroff,
+            index: 0,
+        })
+    }
+}
+
+impl<'a> std::iter::Iterator for SectionIter<'a> {
+    type Item = Result<(Option<Rooted<&'a str>>, Rooted<SectionHeader>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            None
+        } else {
+            Some((|| {
+                let header_offset = self.shoff + self.index * self.step;
+                self.index += 1;
+                let header = {
+                    let (header, header_size) =
+                        SectionHeader::try_from_ctx(&self.bytes[header_offset..], self.ctx)?;
+                    let location = Location {
+                        offset: header_offset,
+                        size: header_size,
+                        ctx: self.ctx,
+                    };
+                    Rooted::new(location, header)
+                };
+                let name = match self.stroff {
+                    Some(stroff) if header.sh_name != 0 => {
+                        let offset = stroff + header.sh_name;
+                        let name: &str = self.bytes.pread(offset)?;
+                        let location = Location {
+                            offset,
+                            size: name.len(),
+                            ctx: self.ctx,
+                        };
+                        Some(Rooted::new(location, name))
+                    }
+                    _ => None,
+                };
+                Ok((name, header))
+            })())
+        }
+    }
+}
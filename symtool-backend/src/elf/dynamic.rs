@@ -0,0 +1,298 @@
+//! Read and modify the strings named by the ELF dynamic section.
+//!
+//! The dynamic section names the shared object (`DT_SONAME`) and its dependencies (`DT_NEEDED`),
+//! among other strings, by their offsets in the dynamic string table, `.dynstr`.  A new name can
+//! replace the original in place if it fits, or an entry can point to a copy of the new name
+//! elsewhere in the string table.  If the string table doesn't contain the new name,
+//! [`grow_strtab`] appends it by moving the string table to a new segment at the end of the file.
+
+use crate::elf::{context_from_elf, SectionIter};
+use crate::error::{Error, Result};
+use crate::patch::{Location, Patch, Rooted};
+use goblin::elf::dynamic::{Dyn, DT_NEEDED, DT_NULL, DT_RPATH, DT_RUNPATH, DT_SONAME};
+use goblin::elf::dynamic::{DT_STRSZ, DT_STRTAB};
+use goblin::elf::header::{ET_DYN, ET_EXEC};
+use goblin::elf::program_header::{ProgramHeader, PF_R, PT_LOAD, PT_PHDR};
+use goblin::elf::section_header::SHT_DYNAMIC;
+use goblin::elf::{Elf, Header};
+use scroll::ctx::{IntoCtx, TryFromCtx, TryIntoCtx};
+use scroll::Pread;
+use std::convert::TryFrom;
+
+/// Names a filtee whose symbols are used in preference to the shared object's own symbols.
+pub const DT_AUXILIARY: u64 = 0x7fff_fffd;
+
+/// Names a filtee whose symbols are used in place of the shared object's own symbols.
+pub const DT_FILTER: u64 = 0x7fff_ffff;
+
+/// Returns true if the value of a dynamic section entry with tag `tag` is an offset into the
+/// dynamic string table.
+pub fn is_string_tag(tag: u64) -> bool {
+    matches!(
+        tag,
+        DT_NEEDED | DT_SONAME | DT_RPATH | DT_RUNPATH | DT_AUXILIARY | DT_FILTER
+    )
+}
+
+/// A dynamic section entry naming a string in the dynamic string table.
+#[derive(Debug)]
+pub struct DynamicString<'a> {
+    /// The dynamic section entry, such as `DT_SONAME` or `DT_NEEDED`
+    pub entry: Rooted<Dyn>,
+
+    /// The string named by the entry
+    pub name: Rooted<&'a str>,
+}
+
+impl<'a> DynamicString<'a> {
+    /// Construct a patch that points the entry to the string at `offset` in the dynamic string
+    /// table.
+    pub fn set_offset(&self, offset: usize) -> Result<Patch> {
+        self.entry.patch_with(Dyn {
+            d_tag: self.entry.d_tag,
+            d_val: offset as u64,
+        })
+    }
+}
+
+/// The dynamic section of an ELF binary, and the strings it names.
+pub struct Dynamic<'a> {
+    /// The entries of the dynamic section, up to and excluding the terminating `DT_NULL`
+    pub entries: Vec<Rooted<Dyn>>,
+
+    /// The entries that name strings, in the order they are stored
+    pub strings: Vec<DynamicString<'a>>,
+
+    strtab: &'a [u8],
+    strtab_index: usize,
+}
+
+impl<'a> Dynamic<'a> {
+    /// Read an ELF binary's `SHT_DYNAMIC` section and its linked string table.
+    ///
+    /// Relocatable objects have no dynamic section.
+    pub fn from_elf(bytes: &'a [u8], elf: &Elf) -> Result<Option<Self>> {
+        let ctx = context_from_elf(elf);
+        let header = match elf
+            .section_headers
+            .iter()
+            .find(|header| header.sh_type == SHT_DYNAMIC)
+        {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let strtab_index = header.sh_link as usize;
+        let strtab_header = elf
+            .section_headers
+            .get(strtab_index)
+            .ok_or_else(|| Error::Malformed("sh_link too large".to_string()))?;
+        let strtab_range = strtab_header.file_range().unwrap_or_default();
+        let strtab = bytes
+            .get(strtab_range)
+            .ok_or_else(|| Error::Malformed("dynamic string table out of bounds".to_string()))?;
+
+        let mut entries = Vec::new();
+        let mut strings = Vec::new();
+        let step = Dyn::size(ctx.container);
+        let count = header.sh_size as usize / step;
+        for index in 0..count {
+            let offset = header.sh_offset as usize + index * step;
+            let (entry, size) = Dyn::try_from_ctx(
+                bytes
+                    .get(offset..)
+                    .ok_or_else(|| Error::Malformed("dynamic section out of bounds".to_string()))?,
+                ctx,
+            )?;
+            if entry.d_tag == DT_NULL {
+                break;
+            }
+            if is_string_tag(entry.d_tag) {
+                let name_offset = strtab_header.sh_offset as usize + entry.d_val as usize;
+                let name: &str = bytes.pread(name_offset)?;
+                let name_location = Location {
+                    offset: name_offset,
+                    size: name.len(),
+                    ctx,
+                };
+                strings.push(DynamicString {
+                    entry: Rooted::new(Location { offset, size, ctx }, entry.clone()),
+                    name: Rooted::new(name_location, name),
+                });
+            }
+            entries.push(Rooted::new(Location { offset, size, ctx }, entry));
+        }
+        Ok(Some(Self {
+            entries,
+            strings,
+            strtab,
+            strtab_index,
+        }))
+    }
+
+    /// The name of the shared object (`DT_SONAME`), if any.
+    pub fn soname(&self) -> Option<&DynamicString<'a>> {
+        self.strings
+            .iter()
+            .find(|string| string.entry.d_tag == DT_SONAME)
+    }
+
+    /// The names of the shared objects this binary depends on (`DT_NEEDED`).
+    pub fn needed(&self) -> impl Iterator<Item = &DynamicString<'a>> {
+        self.strings
+            .iter()
+            .filter(|string| string.entry.d_tag == DT_NEEDED)
+    }
+
+    /// The offset of `name` in the dynamic string table, if it contains the string.
+    ///
+    /// The string may be stored within another string that shares its suffix.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        find_string(self.strtab, name)
+    }
+}
+
+fn find_string(strtab: &[u8], name: &str) -> Option<usize> {
+    let mut needle = name.as_bytes().to_vec();
+    needle.push(0);
+    strtab
+        .windows(needle.len())
+        .position(|window| window == needle.as_slice())
+}
+
+fn align_up(value: u64, align: u64) -> Option<u64> {
+    Some(value.checked_add(align - 1)? / align * align)
+}
+
+/// Construct patches that append `strings` to the dynamic string table of a shared object or
+/// executable, returning them with the new size of the file.
+///
+/// The string table can't grow in place, so the enlarged table is written to a new loadable
+/// segment at the end of the file, along with the program header table, which needs room for the
+/// new segment's header.  The original string table is left in place but unused.  Strings that
+/// the table already contains aren't appended.
+///
+/// GNU `strip` and `objcopy` expect the program header table to follow the file header, and
+/// can't process the result.
+pub fn grow_strtab(bytes: &[u8], elf: &Elf, strings: &[&str]) -> Result<(usize, Vec<Patch>)> {
+    let ctx = context_from_elf(elf);
+    if elf.header.e_type != ET_DYN && elf.header.e_type != ET_EXEC {
+        return Err(Error::InvalidArgument(
+            "Only the string tables of shared objects and executables can grow".to_string(),
+        ));
+    }
+    let dynamic = Dynamic::from_elf(bytes, elf)?
+        .ok_or_else(|| Error::Malformed("no dynamic section".to_string()))?;
+    let mut strtab = dynamic.strtab.to_vec();
+    for string in strings {
+        if find_string(&strtab, string).is_none() {
+            strtab.extend_from_slice(string.as_bytes());
+            strtab.push(0);
+        }
+    }
+
+    // Map the new segment at the same distance from its file offset as the first segment, since
+    // some loaders assume this when locating the program header table
+    let malformed = || Error::Malformed("loadable segments out of range".to_string());
+    let loads: Vec<_> = elf
+        .program_headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .collect();
+    let first = loads
+        .first()
+        .ok_or_else(|| Error::Malformed("no loadable segments".to_string()))?;
+    let align = first.p_align.max(1);
+    let delta = first
+        .p_vaddr
+        .checked_sub(first.p_offset)
+        .ok_or_else(malformed)?;
+    let end = loads
+        .iter()
+        .map(|header| header.p_vaddr.checked_add(header.p_memsz))
+        .collect::<Option<Vec<_>>>()
+        .and_then(|ends| ends.into_iter().max())
+        .ok_or_else(malformed)?;
+    let offset = align_up(bytes.len() as u64, align)
+        .max(align_up(end.saturating_sub(delta), align))
+        .ok_or_else(malformed)?;
+    let vaddr = offset.checked_add(delta).ok_or_else(malformed)?;
+
+    let phentsize = ProgramHeader::size(ctx);
+    if elf.header.e_phentsize as usize != phentsize {
+        return Err(Error::Malformed("unexpected e_phentsize".to_string()));
+    }
+    let phnum = u16::try_from(elf.program_headers.len() + 1)
+        .ok()
+        .filter(|phnum| *phnum < 0xffff)
+        .ok_or_else(|| Error::Malformed("too many program headers".to_string()))?;
+    let phdrs_size = phnum as u64 * phentsize as u64;
+    let phdrs_start = align_up(strtab.len() as u64, 8).ok_or_else(malformed)?;
+    let phdrs_offset = offset + phdrs_start;
+    let phdrs_vaddr = vaddr + phdrs_start;
+    let segment_size = phdrs_start + phdrs_size;
+
+    let mut headers = elf.program_headers.clone();
+    for header in headers.iter_mut().filter(|header| header.p_type == PT_PHDR) {
+        header.p_offset = phdrs_offset;
+        header.p_vaddr = phdrs_vaddr;
+        header.p_paddr = phdrs_vaddr;
+        header.p_filesz = phdrs_size;
+        header.p_memsz = phdrs_size;
+    }
+    // Loadable segments are sorted by address, and the new segment has the highest
+    let position = headers
+        .iter()
+        .rposition(|header| header.p_type == PT_LOAD)
+        .map_or(headers.len(), |position| position + 1);
+    headers.insert(
+        position,
+        ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: PF_R,
+            p_offset: offset,
+            p_vaddr: vaddr,
+            p_paddr: vaddr,
+            p_filesz: segment_size,
+            p_memsz: segment_size,
+            p_align: align,
+        },
+    );
+    let mut segment = vec![0u8; segment_size as usize];
+    segment[..strtab.len()].copy_from_slice(&strtab);
+    for (index, header) in headers.into_iter().enumerate() {
+        header.try_into_ctx(
+            &mut segment[phdrs_start as usize + index * phentsize..],
+            ctx,
+        )?;
+    }
+
+    let mut patches = vec![Patch::at(offset as usize, segment)];
+    let mut header = elf.header;
+    header.e_phoff = phdrs_offset;
+    header.e_phnum = phnum;
+    let mut header_bytes = vec![0u8; Header::size(ctx)];
+    header.into_ctx(&mut header_bytes, ctx);
+    patches.push(Patch::at(0, header_bytes));
+    for entry in &dynamic.entries {
+        let d_val = match entry.d_tag {
+            DT_STRTAB => vaddr,
+            DT_STRSZ => strtab.len() as u64,
+            _ => continue,
+        };
+        patches.push(entry.patch_with(Dyn {
+            d_tag: entry.d_tag,
+            d_val,
+        })?);
+    }
+    for (index, section) in SectionIter::from_elf(bytes, elf)?.enumerate() {
+        let (_, section) = section?;
+        if index == dynamic.strtab_index {
+            let mut header = (*section).clone();
+            header.sh_offset = offset;
+            header.sh_addr = vaddr;
+            header.sh_size = strtab.len() as u64;
+            patches.push(section.patch_with(header)?);
+        }
+    }
+    Ok(((offset + segment_size) as usize, patches))
+}
@@ -0,0 +1,425 @@
+//! Read and modify ELF symbol versions.
+//!
+//! Symbol versions are described by three sections.  `.gnu.version_d` defines the versions of
+//! the symbols in a shared object, `.gnu.version_r` lists the versions required from each
+//! dependency, and `.gnu.version` assigns a version index to each dynamic symbol.  The dynamic
+//! linker matches versions by the hashes of their names, so a renamed version must also be
+//! rehashed.
+
+use crate::elf::context_from_elf;
+use crate::error::{Error, Result};
+use crate::patch::{Location, Patch, Rooted};
+use goblin::container::{Ctx, Endian};
+use goblin::elf::section_header::{SHT_GNU_VERDEF, SHT_GNU_VERNEED, SHT_GNU_VERSYM};
+use goblin::elf::{Elf, SectionHeader};
+use scroll::Pread;
+use std::convert::TryFrom;
+
+/// The version index of local symbols.
+pub const VER_NDX_LOCAL: u16 = 0;
+
+/// The version index of global symbols without a version.
+pub const VER_NDX_GLOBAL: u16 = 1;
+
+/// Set in `vd_flags` for the version definition naming the shared object itself.
+pub const VER_FLG_BASE: u16 = 0x1;
+
+/// Set in a `.gnu.version` entry if the symbol is not the default version of its name.
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+
+/// The hash of a version name, stored in `vd_hash` and `vna_hash`.
+pub fn elf_hash(name: &str) -> u32 {
+    let mut hash: u32 = 0;
+    for byte in name.bytes() {
+        hash = (hash << 4).wrapping_add(byte as u32);
+        let high = hash & 0xf000_0000;
+        if high != 0 {
+            hash ^= high >> 24;
+        }
+        hash &= !high;
+    }
+    hash
+}
+
+fn patch_u32(field: &Rooted<u32>, value: u32) -> Result<Patch> {
+    let value_bytes = match field.location().ctx.le {
+        Endian::Little => value.to_le_bytes(),
+        Endian::Big => value.to_be_bytes(),
+    };
+    field.patch_with_bytes(&value_bytes)
+}
+
+/// Construct a patch that replaces the hash of a version with the hash of `name`.
+pub fn rehash(hash: &Rooted<u32>, name: &str) -> Result<Patch> {
+    patch_u32(hash, elf_hash(name))
+}
+
+/// Construct a patch that points a name field, such as [`Verneed::file_offset`], to the string at
+/// `offset` in the string table.
+pub fn set_string_offset(field: &Rooted<u32>, offset: usize) -> Result<Patch> {
+    let offset = u32::try_from(offset)
+        .map_err(|_| Error::Malformed("string table offset too large".to_string()))?;
+    patch_u32(field, offset)
+}
+
+/// Construct a patch that assigns the version `index` to a `.gnu.version` entry, preserving
+/// whether the symbol is the default version of its name.
+pub fn set_version(entry: &Rooted<u16>, index: u16) -> Result<Patch> {
+    let value = (entry.value & VERSYM_HIDDEN) | (index & !VERSYM_HIDDEN);
+    let value_bytes = match entry.location().ctx.le {
+        Endian::Little => value.to_le_bytes(),
+        Endian::Big => value.to_be_bytes(),
+    };
+    entry.patch_with_bytes(&value_bytes)
+}
+
+/// A version definition, from `.gnu.version_d`.
+#[derive(Debug)]
+pub struct Verdef<'a> {
+    /// The flags (`vd_flags`), such as [`VER_FLG_BASE`]
+    pub flags: u16,
+
+    /// The version index of symbols with this version (`vd_ndx`)
+    pub index: u16,
+
+    /// The hash of the name (`vd_hash`)
+    pub hash: Rooted<u32>,
+
+    /// The name of the version
+    pub name: Rooted<&'a str>,
+
+    /// The offset of the name in the string table (`vda_name` of the first auxiliary entry)
+    pub name_offset: Rooted<u32>,
+
+    /// The names of the versions this version inherits from
+    pub parents: Vec<Rooted<&'a str>>,
+}
+
+/// A version required from a dependency, from `.gnu.version_r`.
+#[derive(Debug)]
+pub struct Vernaux<'a> {
+    /// The flags (`vna_flags`)
+    pub flags: u16,
+
+    /// The version index of symbols with this version (`vna_other`)
+    pub index: u16,
+
+    /// The hash of the name (`vna_hash`)
+    pub hash: Rooted<u32>,
+
+    /// The name of the version
+    pub name: Rooted<&'a str>,
+}
+
+/// The versions required from a dependency, from `.gnu.version_r`.
+#[derive(Debug)]
+pub struct Verneed<'a> {
+    /// The file name of the dependency
+    pub file: Rooted<&'a str>,
+
+    /// The offset of the file name in the string table (`vn_file`)
+    pub file_offset: Rooted<u32>,
+
+    /// The required versions
+    pub versions: Vec<Vernaux<'a>>,
+}
+
+/// Find the section with type `sh_type`, returning it and the offset of its linked string table.
+fn find_section<'e>(elf: &'e Elf, sh_type: u32) -> Result<Option<(&'e SectionHeader, usize)>> {
+    let header = match elf.section_headers.iter().find(|h| h.sh_type == sh_type) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let strtab = elf
+        .section_headers
+        .get(header.sh_link as usize)
+        .ok_or_else(|| Error::Malformed("sh_link too large".to_string()))?;
+    Ok(Some((header, strtab.sh_offset as usize)))
+}
+
+/// Read a string from a string table.
+fn read_str(bytes: &[u8], ctx: Ctx, stroff: usize, offset: u32) -> Result<Rooted<&str>> {
+    let offset = stroff + offset as usize;
+    let name: &str = bytes.pread(offset)?;
+    let location = Location {
+        offset,
+        size: name.len(),
+        ctx,
+    };
+    Ok(Rooted::new(location, name))
+}
+
+/// Read a `u32` field.
+fn read_u32(bytes: &[u8], ctx: Ctx, offset: usize) -> Result<Rooted<u32>> {
+    let value: u32 = bytes.pread_with(offset, ctx.le)?;
+    let location = Location {
+        offset,
+        size: 4,
+        ctx,
+    };
+    Ok(Rooted::new(location, value))
+}
+
+/// An iterator over the version definitions in `.gnu.version_d`.
+pub struct VerdefIter<'a> {
+    bytes: &'a [u8],
+    ctx: Ctx,
+    stroff: usize,
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a> VerdefIter<'a> {
+    /// Construct a `VerdefIter` from an ELF binary's `SHT_GNU_verdef` section.
+    pub fn from_elf(bytes: &'a [u8], elf: &Elf) -> Result<Option<Self>> {
+        Ok(
+            find_section(elf, SHT_GNU_VERDEF)?.map(|(header, stroff)| Self {
+                bytes,
+                ctx: context_from_elf(elf),
+                stroff,
+                offset: header.sh_offset as usize,
+                remaining: header.sh_info as usize,
+            }),
+        )
+    }
+}
+
+impl<'a> std::iter::Iterator for VerdefIter<'a> {
+    type Item = Result<Verdef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some((|| {
+            let (bytes, ctx, offset) = (self.bytes, self.ctx, self.offset);
+            let flags: u16 = bytes.pread_with(offset + 2, ctx.le)?;
+            let index: u16 = bytes.pread_with(offset + 4, ctx.le)?;
+            let count: u16 = bytes.pread_with(offset + 6, ctx.le)?;
+            let hash = read_u32(bytes, ctx, offset + 8)?;
+            let aux: u32 = bytes.pread_with(offset + 12, ctx.le)?;
+            let next: u32 = bytes.pread_with(offset + 16, ctx.le)?;
+
+            // The first auxiliary entry names the version, and the rest name its parents
+            let mut names = Vec::new();
+            let mut aux_offset = offset + aux as usize;
+            let name_offset = read_u32(bytes, ctx, aux_offset)?;
+            for _ in 0..count {
+                let name: u32 = bytes.pread_with(aux_offset, ctx.le)?;
+                names.push(read_str(bytes, ctx, self.stroff, name)?);
+                let aux_next: u32 = bytes.pread_with(aux_offset + 4, ctx.le)?;
+                aux_offset += aux_next as usize;
+            }
+            if names.is_empty() {
+                return Err(Error::Malformed(
+                    "version definition has no name".to_string(),
+                ));
+            }
+            let name = names.remove(0);
+
+            if next == 0 {
+                self.remaining = 0;
+            }
+            self.offset += next as usize;
+            Ok(Verdef {
+                flags,
+                index,
+                hash,
+                name,
+                name_offset,
+                parents: names,
+            })
+        })())
+    }
+}
+
+/// An iterator over the version requirements in `.gnu.version_r`.
+pub struct VerneedIter<'a> {
+    bytes: &'a [u8],
+    ctx: Ctx,
+    stroff: usize,
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a> VerneedIter<'a> {
+    /// Construct a `VerneedIter` from an ELF binary's `SHT_GNU_verneed` section.
+    pub fn from_elf(bytes: &'a [u8], elf: &Elf) -> Result<Option<Self>> {
+        Ok(
+            find_section(elf, SHT_GNU_VERNEED)?.map(|(header, stroff)| Self {
+                bytes,
+                ctx: context_from_elf(elf),
+                stroff,
+                offset: header.sh_offset as usize,
+                remaining: header.sh_info as usize,
+            }),
+        )
+    }
+}
+
+impl<'a> std::iter::Iterator for VerneedIter<'a> {
+    type Item = Result<Verneed<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some((|| {
+            let (bytes, ctx, offset) = (self.bytes, self.ctx, self.offset);
+            let count: u16 = bytes.pread_with(offset + 2, ctx.le)?;
+            let file_offset = read_u32(bytes, ctx, offset + 4)?;
+            let aux: u32 = bytes.pread_with(offset + 8, ctx.le)?;
+            let next: u32 = bytes.pread_with(offset + 12, ctx.le)?;
+
+            let mut versions = Vec::new();
+            let mut aux_offset = offset + aux as usize;
+            for _ in 0..count {
+                let hash = read_u32(bytes, ctx, aux_offset)?;
+                let flags: u16 = bytes.pread_with(aux_offset + 4, ctx.le)?;
+                let index: u16 = bytes.pread_with(aux_offset + 6, ctx.le)?;
+                let name: u32 = bytes.pread_with(aux_offset + 8, ctx.le)?;
+                let aux_next: u32 = bytes.pread_with(aux_offset + 12, ctx.le)?;
+                versions.push(Vernaux {
+                    flags,
+                    index,
+                    hash,
+                    name: read_str(bytes, ctx, self.stroff, name)?,
+                });
+                aux_offset += aux_next as usize;
+            }
+
+            if next == 0 {
+                self.remaining = 0;
+            }
+            self.offset += next as usize;
+            Ok(Verneed {
+                file: read_str(bytes, ctx, self.stroff, file_offset.value)?,
+                file_offset,
+                versions,
+            })
+        })())
+    }
+}
+
+/// An iterator over the entries of `.gnu.version`, which correspond to the entries of the dynamic
+/// symbol table.
+pub struct VersymIter<'a> {
+    bytes: &'a [u8],
+    ctx: Ctx,
+    offset: usize,
+    count: usize,
+    index: usize,
+}
+
+impl<'a> VersymIter<'a> {
+    /// Construct a `VersymIter` from an ELF binary's `SHT_GNU_versym` section.
+    pub fn from_elf(bytes: &'a [u8], elf: &Elf) -> Result<Option<Self>> {
+        let header = elf
+            .section_headers
+            .iter()
+            .find(|header| header.sh_type == SHT_GNU_VERSYM);
+        Ok(header.map(|header| Self {
+            bytes,
+            ctx: context_from_elf(elf),
+            offset: header.sh_offset as usize,
+            count: header.sh_size as usize / 2,
+            index: 0,
+        }))
+    }
+}
+
+impl<'a> std::iter::Iterator for VersymIter<'a> {
+    type Item = Result<Rooted<u16>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+        let offset = self.offset + self.index * 2;
+        self.index += 1;
+        Some(
+            self.bytes
+                .pread_with::<u16>(offset, self.ctx.le)
+                .map_err(Error::from)
+                .map(|value| {
+                    let location = Location {
+                        offset,
+                        size: 2,
+                        ctx: self.ctx,
+                    };
+                    Rooted::new(location, value)
+                }),
+        )
+    }
+}
+
+/// The symbol versions of an ELF binary.
+#[derive(Debug, Default)]
+pub struct Versions<'a> {
+    /// The version definitions
+    pub definitions: Vec<Verdef<'a>>,
+
+    /// The version requirements of each dependency
+    pub requirements: Vec<Verneed<'a>>,
+
+    /// The version index of each dynamic symbol
+    pub symbols: Vec<Rooted<u16>>,
+}
+
+impl<'a> Versions<'a> {
+    /// Read the symbol versions of an ELF binary.
+    ///
+    /// Binaries without symbol versions, such as relocatable objects, have no versions.
+    pub fn from_elf(bytes: &'a [u8], elf: &Elf) -> Result<Self> {
+        fn collect<T>(iter: Option<impl Iterator<Item = Result<T>>>) -> Result<Vec<T>> {
+            iter.map(|iter| iter.collect())
+                .unwrap_or_else(|| Ok(Vec::new()))
+        }
+        Ok(Self {
+            definitions: collect(VerdefIter::from_elf(bytes, elf)?)?,
+            requirements: collect(VerneedIter::from_elf(bytes, elf)?)?,
+            symbols: collect(VersymIter::from_elf(bytes, elf)?)?,
+        })
+    }
+
+    /// The name of the version with index `index`, if any.
+    ///
+    /// The base version definition, which names the shared object itself, is not considered.
+    pub fn name(&self, index: u16) -> Option<&'a str> {
+        let index = index & !VERSYM_HIDDEN;
+        let defined = self
+            .definitions
+            .iter()
+            .filter(|definition| definition.flags & VER_FLG_BASE == 0)
+            .find(|definition| definition.index == index)
+            .map(|definition| definition.name.value);
+        defined.or_else(|| {
+            self.requirements
+                .iter()
+                .flat_map(|requirement| &requirement.versions)
+                .find(|version| version.index == index)
+                .map(|version| version.name.value)
+        })
+    }
+
+    /// The index of the version named `name`, if any.
+    ///
+    /// The base version definition, which names the shared object itself, is not considered.
+    pub fn index(&self, name: &str) -> Option<u16> {
+        let defined = self
+            .definitions
+            .iter()
+            .filter(|definition| definition.flags & VER_FLG_BASE == 0)
+            .find(|definition| definition.name.value == name)
+            .map(|definition| definition.index);
+        defined.or_else(|| {
+            self.requirements
+                .iter()
+                .flat_map(|requirement| &requirement.versions)
+                .find(|version| version.name.value == name)
+                .map(|version| version.index)
+        })
+    }
+}
@@ -1,5 +1,9 @@
 //! Errors returned by this crate.
 
+use crate::object::Context;
+use crate::patch::Patch;
+use crate::report::Rule;
+
 pub type Result<T> = std::result::Result<T, Error>;
 pub type TransformResult<T, E> = std::result::Result<T, TransformError<E>>;
 
@@ -15,6 +19,9 @@ pub enum Error {
     /// An error from scroll, used by the binary parser
     Scroll(scroll::Error),
 
+    /// An invalid regular expression
+    Regex(regex::Error),
+
     /// The loaded object is malformed
     Malformed(String),
 
@@ -35,6 +42,31 @@ pub enum Error {
 
     /// A patch was too big to insert into the binary
     PatchTooBig,
+
+    /// The requested operation is not supported for the loaded object
+    Unsupported(String),
+
+    /// The transformed object differs from the original in unexpected ways
+    Verification(Vec<String>),
+
+    /// Two patches modify the same bytes with different values
+    ConflictingPatches(Patch, Patch),
+
+    /// Renaming strings in place would also rename other strings that share storage, listed with
+    /// the names they would become
+    SharedString(Vec<(String, String)>),
+
+    /// A symbol could not be stripped because it is referenced by a relocation
+    ReferencedSymbol(String),
+
+    /// An operation was requested with invalid arguments
+    InvalidArgument(String),
+
+    /// An error in an object contained in an archive or fat binary
+    Nested(Context, Box<Error>),
+
+    /// Editing rules did not match any names
+    UnmatchedRules(Vec<Rule>),
 }
 
 impl std::fmt::Display for Error {
@@ -43,6 +75,7 @@ impl std::fmt::Display for Error {
             Self::Io(e) => write!(f, "{}", e),
             Self::Goblin(e) => write!(f, "{}", e),
             Self::Scroll(e) => write!(f, "{}", e),
+            Self::Regex(e) => write!(f, "{}", e),
             Self::Malformed(s) => write!(f, "{}", s),
             Self::ReplaceString {
                 original,
@@ -50,12 +83,53 @@ impl std::fmt::Display for Error {
             } => {
                 write!(
                 f,
-                "Replacement string (\"{}\") must be the same size or smaller than the original (\"{}\")", replacement, original)
+                "Replacement string (\"{}\") does not fit in the space occupied by the original (\"{}\")", replacement, original)
             }
             Self::UnknownObject => write!(f, "Unknown object type"),
             Self::FatBinaryUnsupported => write!(f, "Fat MachO binaries are not yet supported"),
             Self::WrongSectionHeader(s) => write!(f, "{}", s),
             Self::PatchTooBig => write!(f, "Patched data too big for original location"),
+            Self::InvalidArgument(s) => write!(f, "{}", s),
+            Self::Unsupported(s) => write!(f, "Unsupported operation: {}", s),
+            Self::ConflictingPatches(first, second) => {
+                write!(f, "Conflicting patches: {} overlaps {}", first, second)
+            }
+            Self::SharedString(affected) => {
+                write!(
+                    f,
+                    "Renamed strings share storage with other strings, which would also be renamed: "
+                )?;
+                for (index, (original, renamed)) in affected.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "\"{}\" would become \"{}\"", original, renamed)?;
+                }
+                Ok(())
+            }
+            Self::Verification(mismatches) => {
+                write!(f, "Verification failed:")?;
+                for mismatch in mismatches {
+                    write!(f, "\n  {}", mismatch)?;
+                }
+                Ok(())
+            }
+            Self::ReferencedSymbol(name) => write!(
+                f,
+                "Symbol \"{}\" is referenced by a relocation and cannot be stripped",
+                name
+            ),
+            Self::Nested(context, e) => write!(f, "{}: {}", context, e),
+            Self::UnmatchedRules(rules) => {
+                write!(f, "Rules did not match any names: ")?;
+                for (index, rule) in rules.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", rule)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -66,6 +140,8 @@ impl std::error::Error for Error {
             Self::Io(e) => Some(e),
             Self::Goblin(e) => Some(e),
             Self::Scroll(e) => Some(e),
+            Self::Regex(e) => Some(e),
+            Self::Nested(_, e) => Some(e.as_ref()),
             _ => None,
         }
     }
@@ -89,6 +165,12 @@ impl From<scroll::Error> for Error {
     }
 }
 
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Self::Regex(err)
+    }
+}
+
 /// An error returned by the object transformer
 #[derive(Debug)]
 pub enum TransformError<T>
@@ -100,6 +182,9 @@ where
 
     /// An error produced by the transformer
     Transform(T),
+
+    /// An error in an object contained in an archive or fat binary
+    Nested(Context, Box<TransformError<T>>),
 }
 
 impl<T> std::fmt::Display for TransformError<T>
@@ -110,6 +195,7 @@ where
         match self {
             Self::SymTool(e) => write!(f, "{}", e),
             Self::Transform(e) => write!(f, "{}", e),
+            Self::Nested(context, e) => write!(f, "{}: {}", context, e),
         }
     }
 }
@@ -122,6 +208,7 @@ where
         match self {
             Self::SymTool(e) => Some(e),
             Self::Transform(e) => e.source(),
+            Self::Nested(_, e) => Some(e.as_ref()),
         }
     }
 }
@@ -135,6 +222,15 @@ where
     }
 }
 
+impl From<TransformError<Error>> for Error {
+    fn from(err: TransformError<Error>) -> Self {
+        match err {
+            TransformError::SymTool(e) | TransformError::Transform(e) => e,
+            TransformError::Nested(context, e) => Self::Nested(context, Box::new((*e).into())),
+        }
+    }
+}
+
 impl<T> From<goblin::error::Error> for TransformError<T>
 where
     T: std::error::Error,
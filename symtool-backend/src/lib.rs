@@ -3,8 +3,18 @@
 //! This is the implementation behind the [symtool](https://github.com/calebzulawski/symtool)
 //! utility.
 
+pub mod archive;
+pub mod check;
+pub mod diff;
+pub mod dwarf;
+pub mod editor;
 pub mod elf;
 pub mod error;
 pub mod mach;
 pub mod object;
 pub mod patch;
+pub mod rename;
+pub mod report;
+pub mod strip;
+pub mod symbol;
+pub mod verify;
@@ -3,13 +3,19 @@
 use crate::error::Result;
 use crate::patch::{Location, Rooted};
 use goblin::container::{Container, Ctx, Endian};
-use goblin::mach::load_command::{CommandVariant, SymtabCommand};
-use goblin::mach::symbols::Nlist;
+use goblin::mach::load_command::{
+    CommandVariant, Section32, Section64, SymtabCommand, SIZEOF_SECTION_32, SIZEOF_SECTION_64,
+    SIZEOF_SEGMENT_COMMAND_32, SIZEOF_SEGMENT_COMMAND_64,
+};
+use goblin::mach::segment::Section;
+use goblin::mach::symbols::{Nlist, N_FUN, N_GSYM, N_STAB, N_STSYM};
 use goblin::mach::MachO;
-use scroll::ctx::{SizeWith, TryFromCtx};
+use scroll::ctx::{SizeWith, StrCtx, TryFromCtx};
 use scroll::Pread;
 
-fn context_from_macho(macho: &MachO) -> Ctx {
+pub mod dysymtab;
+
+pub(crate) fn context_from_macho(macho: &MachO) -> Ctx {
     let container = if macho.is_64 {
         Container::Big
     } else {
@@ -62,6 +68,40 @@ impl<'a> SymtabIter<'a> {
     }
 }
 
+/// Read every section header in a Mach-O binary, along with the offset of each header.
+///
+/// Sections are returned in the order they appear in the load commands, which is the order used
+/// for section ordinals (`n_sect` and local relocations).
+pub(crate) fn section_headers(bytes: &[u8], mach: &MachO) -> Result<Vec<(usize, Section)>> {
+    let ctx = context_from_macho(mach);
+    let mut sections = Vec::new();
+    for command in &mach.load_commands {
+        let (start, count, size) = match command.command {
+            CommandVariant::Segment32(segment) => (
+                command.offset + SIZEOF_SEGMENT_COMMAND_32,
+                segment.nsects as usize,
+                SIZEOF_SECTION_32,
+            ),
+            CommandVariant::Segment64(segment) => (
+                command.offset + SIZEOF_SEGMENT_COMMAND_64,
+                segment.nsects as usize,
+                SIZEOF_SECTION_64,
+            ),
+            _ => continue,
+        };
+        for index in 0..count {
+            let offset = start + index * size;
+            let section: Section = if mach.is_64 {
+                bytes.pread_with::<Section64>(offset, ctx.le)?.into()
+            } else {
+                bytes.pread_with::<Section32>(offset, ctx.le)?.into()
+            };
+            sections.push((offset, section));
+        }
+    }
+    Ok(sections)
+}
+
 impl<'a> std::iter::Iterator for SymtabIter<'a> {
     type Item = Result<(Option<Rooted<&'a str>>, Rooted<Nlist>)>;
 
@@ -83,7 +123,7 @@ impl<'a> std::iter::Iterator for SymtabIter<'a> {
                     Rooted::new(location, nlist)
                 };
                 let name = if nlist.n_strx != 0 {
-                    let offset = self.stroff + nlist.n_strx as usize;
+                    let offset = self.stroff + nlist.n_strx;
                     let name: &str = self.bytes.pread(offset)?;
                     let location = Location {
                         offset,
@@ -99,3 +139,107 @@ impl<'a> std::iter::Iterator for SymtabIter<'a> {
         }
     }
 }
+
+/// Returns true if the symbol is a STABS debugging entry that names a function or variable.
+///
+/// `N_FUN`, `N_STSYM`, and `N_GSYM` entries carry the name of the symbol they describe, which
+/// dsymutil uses to link the debug map to the symbol table, so they must be renamed along with
+/// the symbol.  Other debugging entries, such as source and object file paths, are not symbol
+/// names.
+pub fn is_symbol_stab(nlist: &Nlist) -> bool {
+    nlist.n_type & N_STAB != 0 && [N_FUN, N_STSYM, N_GSYM].contains(&nlist.n_type)
+}
+
+/// The size of the fixed-length name fields in section and segment commands.
+const NAME_SIZE: usize = 16;
+
+/// Read a fixed-length name field, which is NUL-terminated only if shorter than the field.
+fn fixed_name(bytes: &[u8], offset: usize, ctx: Ctx) -> Result<Rooted<&str>> {
+    let name: &str = bytes.pread_with(offset, StrCtx::DelimiterUntil(0, NAME_SIZE))?;
+    let location = Location {
+        offset,
+        size: NAME_SIZE,
+        ctx,
+    };
+    Ok(Rooted::new(location, name))
+}
+
+/// An iterator over Mach-O section headers.
+///
+/// Each item contains the segment name, section name, and section header.  Section names are
+/// stored in fixed-length fields, so they may be replaced with any name up to 16 bytes long.
+pub struct SectionIter<'a> {
+    bytes: &'a [u8],
+    ctx: Ctx,
+    sections: std::vec::IntoIter<(usize, Section)>,
+}
+
+impl<'a> SectionIter<'a> {
+    /// Construct a `SectionIter` from the `LC_SEGMENT` and `LC_SEGMENT_64` load commands of a
+    /// Mach-O binary.
+    pub fn from_mach(bytes: &'a [u8], mach: &MachO) -> Result<Self> {
+        Ok(Self {
+            bytes,
+            ctx: context_from_macho(mach),
+            sections: section_headers(bytes, mach)?.into_iter(),
+        })
+    }
+}
+
+impl<'a> std::iter::Iterator for SectionIter<'a> {
+    type Item = Result<(Rooted<&'a str>, Rooted<&'a str>, Rooted<Section>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (offset, section) = self.sections.next()?;
+        Some((|| {
+            let sectname = fixed_name(self.bytes, offset, self.ctx)?;
+            let segname = fixed_name(self.bytes, offset + NAME_SIZE, self.ctx)?;
+            let location = Location {
+                offset,
+                size: Section::size_with(&self.ctx),
+                ctx: self.ctx,
+            };
+            Ok((segname, sectname, Rooted::new(location, section)))
+        })())
+    }
+}
+
+/// An iterator over Mach-O segment names.
+///
+/// Segment names are stored in fixed-length fields, so they may be replaced with any name up to
+/// 16 bytes long.  Note that each section header also contains the name of its segment.
+pub struct SegmentIter<'a> {
+    bytes: &'a [u8],
+    ctx: Ctx,
+    offsets: std::vec::IntoIter<usize>,
+}
+
+impl<'a> SegmentIter<'a> {
+    /// Construct a `SegmentIter` from the `LC_SEGMENT` and `LC_SEGMENT_64` load commands of a
+    /// Mach-O binary.
+    pub fn from_mach(bytes: &'a [u8], mach: &MachO) -> Self {
+        let offsets = mach
+            .load_commands
+            .iter()
+            .filter_map(|command| match command.command {
+                CommandVariant::Segment32(_) | CommandVariant::Segment64(_) => Some(command.offset),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        Self {
+            bytes,
+            ctx: context_from_macho(mach),
+            offsets: offsets.into_iter(),
+        }
+    }
+}
+
+impl<'a> std::iter::Iterator for SegmentIter<'a> {
+    type Item = Result<Rooted<&'a str>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The segment name follows the `cmd` and `cmdsize` fields
+        let offset = self.offsets.next()? + 8;
+        Some(fixed_name(self.bytes, offset, self.ctx))
+    }
+}
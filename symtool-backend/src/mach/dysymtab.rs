@@ -0,0 +1,298 @@
+//! Maintain the symbol partitions described by `LC_DYSYMTAB`.
+//!
+//! The dynamic symbol table command partitions the symbol table into three contiguous ranges:
+//! local symbols, externally defined symbols, and undefined symbols.  The external and undefined
+//! ranges must each be sorted by name, since ld64 binary searches them.  Renaming a symbol or
+//! changing its binding can break these invariants, in which case the symbol table must be
+//! reordered and every reference to a symbol index updated to match.
+
+use crate::error::{Error, Result};
+use crate::mach::{context_from_macho, section_headers, SymtabIter};
+use crate::patch::{Location, Patch};
+use goblin::container::{Ctx, Endian};
+use goblin::mach::load_command::{CommandVariant, DysymtabCommand, SIZEOF_DYSYMTAB_COMMAND};
+use goblin::mach::relocation::SIZEOF_RELOCATION_INFO;
+use goblin::mach::symbols::{Nlist, N_EXT, N_STAB};
+use goblin::mach::MachO;
+use scroll::ctx::SizeWith;
+use scroll::{Pread, Pwrite};
+
+/// Indirect symbol table entry for a local symbol that has been stripped.
+pub const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+
+/// Indirect symbol table entry for an absolute symbol that has been stripped.
+pub const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+/// Set in `r_address` for scattered relocation entries, which do not refer to symbols.
+const R_SCATTERED: u32 = 0x8000_0000;
+
+/// The partition of the symbol table a symbol belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Partition {
+    /// Local symbols, including debugging entries
+    Local,
+
+    /// Externally visible symbols defined in this object, including private externs
+    ExternalDefined,
+
+    /// Externally visible symbols that are undefined or common
+    Undefined,
+}
+
+impl Partition {
+    /// Determine the partition a symbol belongs to.
+    pub fn of(nlist: &Nlist) -> Self {
+        if nlist.n_type & N_STAB != 0 || nlist.n_type & N_EXT == 0 {
+            Self::Local
+        } else if nlist.is_undefined() {
+            Self::Undefined
+        } else {
+            Self::ExternalDefined
+        }
+    }
+}
+
+/// Find the `LC_DYSYMTAB` load command and its offset in the binary.
+pub(crate) fn find_dysymtab(mach: &MachO) -> Option<(usize, DysymtabCommand)> {
+    mach.load_commands
+        .iter()
+        .find_map(|command| match command.command {
+            CommandVariant::Dysymtab(dysymtab) => Some((command.offset, dysymtab)),
+            _ => None,
+        })
+}
+
+/// Read the partition and name of every symbol in the symbol table.
+fn read_symbols<'a>(bytes: &'a [u8], mach: &MachO) -> Result<Vec<(Partition, &'a str, Nlist)>> {
+    let iter = match SymtabIter::from_mach(bytes, mach) {
+        Some(iter) => iter,
+        None => return Ok(Vec::new()),
+    };
+    iter.map(|symbol| {
+        let (name, nlist) = symbol?;
+        let name = name.map(|name| name.value).unwrap_or("");
+        Ok((Partition::of(&nlist), name, nlist.value))
+    })
+    .collect()
+}
+
+fn is_sorted(symbols: &[(Partition, &str, Nlist)]) -> bool {
+    symbols.windows(2).all(|pair| pair[0].1 <= pair[1].1)
+}
+
+/// Returns true if the symbol table is correctly partitioned by `LC_DYSYMTAB`.
+///
+/// Binaries without an `LC_DYSYMTAB` load command are always considered valid.
+pub fn is_partitioned(bytes: &[u8], mach: &MachO) -> Result<bool> {
+    let dysymtab = match find_dysymtab(mach) {
+        Some((_, dysymtab)) => dysymtab,
+        None => return Ok(true),
+    };
+    let symbols = read_symbols(bytes, mach)?;
+    Ok(check_partitions(&dysymtab, &symbols))
+}
+
+fn check_partitions(dysymtab: &DysymtabCommand, symbols: &[(Partition, &str, Nlist)]) -> bool {
+    let ranges = [
+        (Partition::Local, dysymtab.ilocalsym, dysymtab.nlocalsym),
+        (
+            Partition::ExternalDefined,
+            dysymtab.iextdefsym,
+            dysymtab.nextdefsym,
+        ),
+        (Partition::Undefined, dysymtab.iundefsym, dysymtab.nundefsym),
+    ];
+    let mut next = 0;
+    for (partition, start, count) in ranges.iter() {
+        let (start, count) = (*start as usize, *count as usize);
+        if start != next || start + count > symbols.len() {
+            return false;
+        }
+        let range = &symbols[start..start + count];
+        if range.iter().any(|symbol| symbol.0 != *partition) {
+            return false;
+        }
+        if *partition != Partition::Local && !is_sorted(range) {
+            return false;
+        }
+        next = start + count;
+    }
+    next == symbols.len()
+}
+
+/// Extract the symbol index referenced by a relocation entry, if any.
+///
+/// The `r_info` bitfield is laid out according to the endianness of the binary.
+pub(crate) fn relocation_symbol(r_info: u32, endian: Endian) -> Option<u32> {
+    match endian {
+        Endian::Little if r_info & 0x0800_0000 != 0 => Some(r_info & 0x00ff_ffff),
+        Endian::Big if r_info & 0x0000_0010 != 0 => Some(r_info >> 8),
+        _ => None,
+    }
+}
+
+/// Replace the symbol index referenced by a relocation entry.
+pub(crate) fn with_relocation_symbol(r_info: u32, endian: Endian, symbol: u32) -> u32 {
+    match endian {
+        Endian::Little => (r_info & 0xff00_0000) | symbol,
+        Endian::Big => (r_info & 0x0000_00ff) | (symbol << 8),
+    }
+}
+
+/// Find the offset of every non-scattered relocation entry, in sections and in the external
+/// relocation table (if there is an `LC_DYSYMTAB` load command).
+pub(crate) fn relocation_offsets(
+    bytes: &[u8],
+    mach: &MachO,
+    dysymtab: Option<&DysymtabCommand>,
+) -> Result<Vec<usize>> {
+    let ctx = context_from_macho(mach);
+    let mut relocations = section_headers(bytes, mach)?
+        .into_iter()
+        .map(|(_, section)| (section.reloff as usize, section.nreloc as usize))
+        .collect::<Vec<_>>();
+    if let Some(dysymtab) = dysymtab {
+        relocations.push((dysymtab.extreloff as usize, dysymtab.nextrel as usize));
+    }
+    let mut offsets = Vec::new();
+    for (reloff, nreloc) in relocations {
+        for index in 0..nreloc {
+            let offset = reloff + index * SIZEOF_RELOCATION_INFO;
+            let r_address: u32 = bytes.pread_with(offset, ctx.le)?;
+            if !mach.is_64 && r_address & R_SCATTERED != 0 {
+                continue;
+            }
+            offsets.push(offset);
+        }
+    }
+    Ok(offsets)
+}
+
+pub(crate) fn patch_u32(offset: usize, value: u32, ctx: Ctx) -> Result<Patch> {
+    let mut buf = [0u8; 4];
+    buf.pwrite_with(value, 0, ctx.le)?;
+    Patch::from_bytes(
+        &Location {
+            offset,
+            size: buf.len(),
+            ctx,
+        },
+        &buf,
+    )
+}
+
+/// Produce patches that repartition the symbol table, if necessary.
+///
+/// If the symbol table is already correctly partitioned, no patches are returned.  Otherwise, the
+/// symbol table is reordered into local, externally defined, and undefined symbols (sorting the
+/// latter two by name), `LC_DYSYMTAB` is updated, and the indirect symbol table and relocation
+/// entries are remapped to the new symbol indices.
+pub fn repartition(bytes: &[u8], mach: &MachO) -> Result<Vec<Patch>> {
+    let (command_offset, dysymtab) = match find_dysymtab(mach) {
+        Some(command) => command,
+        None => return Ok(Vec::new()),
+    };
+    let symbols = read_symbols(bytes, mach)?;
+    if check_partitions(&dysymtab, &symbols) {
+        return Ok(Vec::new());
+    }
+    if dysymtab.ntoc != 0 || dysymtab.nmodtab != 0 || dysymtab.nextrefsyms != 0 {
+        return Err(Error::Unsupported(
+            "repartitioning symbols in binaries with a table of contents, module table, or referenced symbol table".to_string(),
+        ));
+    }
+
+    // Stable sorts preserve the original order of local symbols, which are not sorted by name
+    let mut order = (0..symbols.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| {
+        let (a, b) = (&symbols[*a], &symbols[*b]);
+        match (a.0, b.0) {
+            (Partition::Local, Partition::Local) => std::cmp::Ordering::Equal,
+            _ => a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)),
+        }
+    });
+    let mut remap = vec![0u32; symbols.len()];
+    for (new, old) in order.iter().enumerate() {
+        remap[*old] = new as u32;
+    }
+    let count = |partition| symbols.iter().filter(|s| s.0 == partition).count() as u32;
+
+    let ctx = context_from_macho(mach);
+    let mut patches = Vec::new();
+
+    // Rewrite the symbol table in its new order
+    let symoff = mach
+        .load_commands
+        .iter()
+        .find_map(|command| match command.command {
+            CommandVariant::Symtab(symtab) => Some(symtab.symoff as usize),
+            _ => None,
+        })
+        .ok_or_else(|| Error::Malformed("LC_DYSYMTAB without LC_SYMTAB".to_string()))?;
+    let nlist_size = Nlist::size_with(&ctx);
+    for (new, old) in order.iter().enumerate() {
+        if new != *old {
+            let location = Location {
+                offset: symoff + new * nlist_size,
+                size: nlist_size,
+                ctx,
+            };
+            patches.push(Patch::from_ctx(&location, symbols[*old].2.clone())?);
+        }
+    }
+
+    // Update the partitions
+    let nlocalsym = count(Partition::Local);
+    let nextdefsym = count(Partition::ExternalDefined);
+    let nundefsym = count(Partition::Undefined);
+    let dysymtab = DysymtabCommand {
+        ilocalsym: 0,
+        nlocalsym,
+        iextdefsym: nlocalsym,
+        nextdefsym,
+        iundefsym: nlocalsym + nextdefsym,
+        nundefsym,
+        ..dysymtab
+    };
+    let mut command = [0u8; SIZEOF_DYSYMTAB_COMMAND];
+    command.pwrite_with(dysymtab, 0, ctx.le)?;
+    patches.push(Patch::from_bytes(
+        &Location {
+            offset: command_offset,
+            size: command.len(),
+            ctx,
+        },
+        &command,
+    )?);
+
+    // Remap the indirect symbol table
+    for index in 0..dysymtab.nindirectsyms as usize {
+        let offset = dysymtab.indirectsymoff as usize + index * 4;
+        let symbol: u32 = bytes.pread_with(offset, ctx.le)?;
+        if symbol & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) != 0 {
+            continue;
+        }
+        let remapped = *remap.get(symbol as usize).ok_or_else(|| {
+            Error::Malformed(format!("indirect symbol index {} out of range", symbol))
+        })?;
+        if remapped != symbol {
+            patches.push(patch_u32(offset, remapped, ctx)?);
+        }
+    }
+
+    // Remap section relocations and external relocations
+    for offset in relocation_offsets(bytes, mach, Some(&dysymtab))? {
+        let r_info: u32 = bytes.pread_with(offset + 4, ctx.le)?;
+        if let Some(symbol) = relocation_symbol(r_info, ctx.le) {
+            let remapped = *remap.get(symbol as usize).ok_or_else(|| {
+                Error::Malformed(format!("relocation symbol index {} out of range", symbol))
+            })?;
+            if remapped != symbol {
+                let r_info = with_relocation_symbol(r_info, ctx.le, remapped);
+                patches.push(patch_u32(offset + 4, r_info, ctx)?);
+            }
+        }
+    }
+
+    Ok(patches)
+}
@@ -1,10 +1,14 @@
 //! Apply transformations to an object.
 
-use crate::error::{Error, TransformError, TransformResult};
-use crate::patch::Patch;
+use crate::error::{Error, Result, TransformError, TransformResult};
+use crate::patch::{check_conflicts, Patch};
 use goblin::elf::Elf;
-use goblin::mach::MachO;
+use goblin::mach::constants::cputype::get_arch_name_from_types;
+use goblin::mach::fat::{FatArch, FAT_MAGIC};
+use goblin::mach::{MachO, MultiArch};
+use regex::RegexSet;
 use std::convert::TryInto;
+use std::ops::Range;
 
 /// A generic object type
 pub enum Object<'a> {
@@ -19,45 +23,529 @@ pub enum Object<'a> {
 pub type ObjectTransform<Error> =
     dyn for<'a> Fn(&'a [u8], Object) -> std::result::Result<Vec<Patch>, Error>;
 
-/// Apply a transformation to a binary or an archive of binaries.
+/// The type of a transformation applied to an object, given its [`Context`].
 ///
-/// Objects are parsed from `reader` and stored into `writer`.
-/// This function supports both BSD and GNU style archives.
-pub fn transform_object<E>(
-    object: &mut [u8],
-    transformation: &ObjectTransform<E>,
-) -> TransformResult<(), E>
+/// Like [`ObjectTransform`], but also receives the location of the object within an archive or
+/// fat binary.  Use [`without_context`] to adapt an [`ObjectTransform`].
+pub type ContextTransform<Error> =
+    dyn for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<Vec<Patch>, Error>;
+
+/// Adapt a transformation that does not use the [`Context`] to a [`ContextTransform`].
+pub fn without_context<'f, E, F>(
+    transformation: &'f F,
+) -> impl for<'a, 'b, 'c> Fn(&'a [u8], Object<'b>, &'c Context) -> std::result::Result<Vec<Patch>, E> + 'f
 where
-    E: std::error::Error,
+    E: 'f,
+    F: for<'a> Fn(&'a [u8], Object) -> std::result::Result<Vec<Patch>, E> + ?Sized,
 {
-    // Determine the location of the object(s) to manipulate
+    move |bytes: &[u8], object: Object, _: &Context| transformation(bytes, object)
+}
+
+/// The format of an archive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// System V or GNU `ar` format, used on Linux, where long member names are stored in a
+    /// separate `//` member
+    Gnu,
+
+    /// BSD `ar` format, used on macOS, where long member names are stored before the contents of
+    /// each member (`#1/`)
+    Bsd,
+}
+
+/// An object stored in an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Member {
+    /// The name of the member
+    pub name: String,
+
+    /// The position of the member in the archive, starting at 0
+    pub index: usize,
+
+    /// The byte offset of the member's contents in the archive
+    pub offset: usize,
+}
+
+impl std::fmt::Display for Member {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "archive member {} (index {}, offset {:#x})",
+            self.name, self.index, self.offset
+        )
+    }
+}
+
+/// The location of an object within an archive or Mach-O fat binary.
+///
+/// Fat binaries may contain archives, in which case both the slice and the member are known.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    /// The slice of the fat binary containing the object, if any
+    pub fat_arch: Option<FatArch>,
+
+    /// The format of the archive containing the object, if any
+    pub archive: Option<ArchiveKind>,
+
+    /// The archive member containing the object, if any
+    pub member: Option<Member>,
+}
+
+impl Context {
+    /// Returns true if the object is contained in an archive or fat binary, rather than being
+    /// the entire binary.
+    pub fn is_nested(&self) -> bool {
+        self.fat_arch.is_some() || self.member.is_some()
+    }
+
+    /// The name of the architecture of the fat binary slice containing the object, if any.
+    pub fn architecture(&self) -> Option<&'static str> {
+        self.fat_arch
+            .and_then(|arch| get_arch_name_from_types(arch.cputype(), arch.cpusubtype()))
+    }
+}
+
+impl std::fmt::Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.architecture(), self.fat_arch) {
+            (Some(name), _) => write!(f, "{} slice", name)?,
+            (None, Some(arch)) => write!(f, "slice with CPU type {:#x}", arch.cputype())?,
+            (None, None) if self.member.is_none() => write!(f, "object")?,
+            (None, None) => {}
+        }
+        if let Some(member) = &self.member {
+            if self.fat_arch.is_some() {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", member)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for applying a transformation to a binary or an archive of binaries.
+#[derive(Clone, Debug, Default)]
+pub struct TransformOptions {
+    /// Leave archive members that are not ELF or Mach-O objects (such as LLVM bitcode or text
+    /// files) unmodified, rather than failing
+    pub skip_unknown: bool,
+
+    /// Only transform archive members with names matching one of these patterns
+    pub members: Option<RegexSet>,
+
+    /// Leave archive members with names matching any of these patterns unmodified
+    pub exclude_members: Option<RegexSet>,
+}
+
+impl TransformOptions {
+    /// Returns true if the object in `buf` should be skipped.
+    fn skips(&self, context: &Context, buf: &[u8]) -> bool {
+        let selected = context
+            .member
+            .as_ref()
+            .is_none_or(|member| self.selects(&member.name));
+        !selected || (context.is_nested() && self.skip_unknown && !is_known_object(buf))
+    }
+
+    /// Returns true if the archive member named `name` should be transformed.
+    pub(crate) fn selects(&self, name: &str) -> bool {
+        let included = self
+            .members
+            .as_ref()
+            .is_none_or(|members| members.is_match(name));
+        let excluded = self
+            .exclude_members
+            .as_ref()
+            .is_some_and(|members| members.is_match(name));
+        included && !excluded
+    }
+}
+
+/// The location of an object in a binary.
+pub(crate) struct ObjectRange {
+    pub context: Context,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// Determine the format of an archive from the names of its members.
+fn archive_kind(archive: &goblin::archive::Archive) -> ArchiveKind {
+    // GNU names are terminated by a slash, and long names are an offset following a slash
+    let gnu = (0..archive.len()).any(|index| {
+        let name = archive
+            .get_at(index)
+            .unwrap()
+            .raw_name()
+            .trim_end_matches(' ');
+        name.starts_with('/') || name.ends_with('/')
+    });
+    if gnu {
+        ArchiveKind::Gnu
+    } else {
+        ArchiveKind::Bsd
+    }
+}
+
+/// Determine the location of the object(s) in a binary, an archive of binaries, or a Mach-O fat
+/// binary.
+///
+/// Objects are returned in the order they are stored in the binary.
+pub(crate) fn object_ranges(object: &[u8]) -> Result<Vec<ObjectRange>> {
     let mut objects = Vec::new();
+    if goblin::mach::peek(object, 0).ok() == Some(FAT_MAGIC) {
+        if let Ok(fat) = MultiArch::new(object) {
+            for arch in fat.arches()? {
+                let offset = arch.offset as usize;
+                let size = arch.size as usize;
+                if offset
+                    .checked_add(size)
+                    .is_none_or(|end| end > object.len())
+                {
+                    return Err(Error::Malformed(
+                        "fat binary slice extends past the end of the file".to_string(),
+                    ));
+                }
+                push_ranges(
+                    &object[offset..offset + size],
+                    offset,
+                    Some(arch),
+                    &mut objects,
+                )?;
+            }
+            objects.sort_by_key(|object| object.offset);
+            return Ok(objects);
+        }
+    }
+    push_ranges(object, 0, None, &mut objects)?;
+    Ok(objects)
+}
+
+/// Determine the location of the object(s) in a binary or an archive of binaries, at `base` in a
+/// fat binary slice.
+fn push_ranges(
+    object: &[u8],
+    base: usize,
+    fat_arch: Option<FatArch>,
+    objects: &mut Vec<ObjectRange>,
+) -> Result<()> {
     if let Ok(archive) = goblin::archive::Archive::parse(object) {
-        for i in 0..archive.len() {
-            let member = archive.get_at(i).unwrap();
-            objects.push((
-                member.offset.try_into().expect("object too large to parse"),
-                member.header.size,
+        let kind = archive_kind(&archive);
+        for index in 0..archive.len() {
+            let member = archive.get_at(index).unwrap();
+            let offset: usize = member.offset.try_into().expect("object too large to parse");
+            if offset
+                .checked_add(member.header.size)
+                .is_none_or(|end| end > object.len())
+            {
+                return Err(Error::Malformed(
+                    "archive member extends past the end of the file".to_string(),
+                ));
+            }
+            objects.push(ObjectRange {
+                context: Context {
+                    fat_arch,
+                    archive: Some(kind),
+                    member: Some(Member {
+                        name: member.extended_name().to_string(),
+                        index,
+                        offset,
+                    }),
+                },
+                offset: base + offset,
+                size: member.header.size,
+            });
+        }
+    } else {
+        objects.push(ObjectRange {
+            context: Context {
+                fat_arch,
+                ..Context::default()
+            },
+            offset: base,
+            size: object.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Returns true if `buf` is an ELF or Mach-O object (including Mach-O fat binaries).
+pub(crate) fn is_known_object(buf: &[u8]) -> bool {
+    match buf.get(..16).and_then(|magic| magic.try_into().ok()) {
+        Some(magic) => matches!(
+            goblin::peek_bytes(magic),
+            Ok(goblin::Hint::Elf(_)) | Ok(goblin::Hint::Mach(_)) | Ok(goblin::Hint::MachFat(_))
+        ),
+        None => false,
+    }
+}
+
+/// Split a binary, an archive of binaries, or a fat binary into the offset and bytes of each
+/// object.
+fn split_objects(object: &mut [u8]) -> Result<Vec<(Context, usize, &mut [u8])>> {
+    let mut objects = Vec::new();
+    let mut rest = object;
+    let mut consumed = 0;
+    for range in object_ranges(rest)? {
+        if range.offset < consumed {
+            return Err(Error::Malformed(
+                "archive members or fat binary slices overlap".to_string(),
             ));
         }
+        let (_, tail) = std::mem::take(&mut rest).split_at_mut(range.offset - consumed);
+        let (buf, tail) = tail.split_at_mut(range.size);
+        objects.push((range.context, range.offset, buf));
+        rest = tail;
+        consumed = range.offset + range.size;
+    }
+    Ok(objects)
+}
+
+/// The additional output of a transformation of an object, and the ranges of the binary it
+/// modified.
+pub(crate) type Transformed<T> = (T, Vec<Range<usize>>);
+
+/// Apply a transformation to a single object at `offset` in the binary, returning the additional
+/// output of the transformation, or `None` if the object was skipped.
+///
+/// Errors are annotated with the context of objects in archives and fat binaries.
+fn transform_nested<T, E, F>(
+    context: Context,
+    offset: usize,
+    buf: &mut [u8],
+    options: &TransformOptions,
+    transformation: &F,
+) -> TransformResult<Option<Transformed<T>>, E>
+where
+    E: std::error::Error,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<(Vec<Patch>, T), E> + ?Sized,
+{
+    if options.skips(&context, buf) {
+        return Ok(None);
+    }
+    let result = transform_buffer(buf, &context, transformation).map(|(output, patched)| {
+        let patched = patched
+            .into_iter()
+            .map(|range| offset + range.start..offset + range.end)
+            .collect();
+        (output, patched)
+    });
+    in_context(context, result).map(Some)
+}
+
+/// Annotate an error with the context of an object in an archive or fat binary.
+fn in_context<T, E>(context: Context, result: TransformResult<T, E>) -> TransformResult<T, E>
+where
+    E: std::error::Error,
+{
+    if context.is_nested() {
+        result.map_err(|e| TransformError::Nested(context, Box::new(e)))
     } else {
-        objects.push((0, object.len()));
+        result
     }
+}
 
-    // Transform each object
-    for (offset, size) in objects {
-        let buf = &mut object[offset..offset + size];
-        let object = match goblin::Object::parse(&buf)? {
-            goblin::Object::Elf(elf) => Ok(Object::Elf(Box::new(elf))),
-            goblin::Object::Mach(goblin::mach::Mach::Binary(macho)) => {
-                Ok(Object::MachO(Box::new(macho)))
-            }
-            _ => Err(Error::UnknownObject),
-        }?;
-        let patches = transformation(&buf, object).map_err(TransformError::Transform)?;
-        for patch in patches {
+pub(crate) fn parse_object(buf: &[u8]) -> Result<Object<'_>> {
+    match goblin::Object::parse(buf)? {
+        goblin::Object::Elf(elf) => Ok(Object::Elf(Box::new(elf))),
+        goblin::Object::Mach(goblin::mach::Mach::Binary(macho)) => {
+            Ok(Object::MachO(Box::new(macho)))
+        }
+        _ => Err(Error::UnknownObject),
+    }
+}
+
+/// Apply a transformation to an object, returning its additional output and the ranges of the
+/// object that were modified.
+fn transform_buffer<T, E, F>(
+    buf: &mut [u8],
+    context: &Context,
+    transformation: &F,
+) -> TransformResult<Transformed<T>, E>
+where
+    E: std::error::Error,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<(Vec<Patch>, T), E> + ?Sized,
+{
+    let object = parse_object(buf)?;
+    let is_macho = matches!(object, Object::MachO(_));
+    let (patches, output) =
+        transformation(buf, object, context).map_err(TransformError::Transform)?;
+    check_conflicts(&patches)?;
+    let mut patched = Vec::new();
+    for patch in patches {
+        patch.apply(buf);
+        patched.push(patch.range());
+    }
+
+    // Changing symbol names or bindings may invalidate the LC_DYSYMTAB partitions
+    if !patched.is_empty() && is_macho {
+        for patch in repartition_macho(buf)? {
             patch.apply(buf);
+            patched.push(patch.range());
         }
     }
-    Ok(())
+    Ok((output, patched))
+}
+
+/// Apply a transformation to each object in a binary, in order.
+///
+/// Returns the result for each object, stopping at the first error.
+pub(crate) fn transform_each<T, E, F>(
+    object: &mut [u8],
+    options: &TransformOptions,
+    transformation: &F,
+) -> Vec<TransformResult<Option<Transformed<T>>, E>>
+where
+    E: std::error::Error,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<(Vec<Patch>, T), E> + ?Sized,
+{
+    let objects = match split_objects(object) {
+        Ok(objects) => objects,
+        Err(e) => return vec![Err(e.into())],
+    };
+    let mut results = Vec::new();
+    for (context, offset, buf) in objects {
+        let result = transform_nested(context, offset, buf, options, transformation);
+        let failed = result.is_err();
+        results.push(result);
+        if failed {
+            break;
+        }
+    }
+    results
+}
+
+/// Apply a transformation to each object in a binary, in parallel.
+///
+/// Returns the result for each object, in order.  Every object is transformed, even if another
+/// fails.
+#[cfg(feature = "rayon")]
+pub(crate) fn transform_each_parallel<T, E, F>(
+    object: &mut [u8],
+    options: &TransformOptions,
+    transformation: &F,
+) -> Vec<TransformResult<Option<Transformed<T>>, E>>
+where
+    T: Send,
+    E: std::error::Error + Send,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<(Vec<Patch>, T), E>
+        + Sync
+        + ?Sized,
+{
+    use rayon::prelude::*;
+    match split_objects(object) {
+        Ok(objects) => objects
+            .into_par_iter()
+            .map(|(context, offset, buf)| {
+                transform_nested(context, offset, buf, options, transformation)
+            })
+            .collect(),
+        Err(e) => vec![Err(e.into())],
+    }
+}
+
+/// Apply a transformation to a binary or an archive of binaries.
+///
+/// Objects are parsed from `reader` and stored into `writer`.
+/// This function supports both BSD and GNU style archives, as well as Mach-O fat binaries.  The
+/// transformation may be an [`ObjectTransform`] or any closure with the same signature.
+pub fn transform_object<E, F>(object: &mut [u8], transformation: &F) -> TransformResult<(), E>
+where
+    E: std::error::Error,
+    F: for<'a> Fn(&'a [u8], Object) -> std::result::Result<Vec<Patch>, E> + ?Sized,
+{
+    transform_object_with(
+        object,
+        &TransformOptions::default(),
+        &without_context(transformation),
+    )
+}
+
+/// Apply a transformation to a binary or an archive of binaries, with the given options.
+///
+/// The transformation may be a [`ContextTransform`] or any closure with the same signature.
+/// Errors in objects contained in archives or fat binaries are returned as
+/// [`TransformError::Nested`].
+pub fn transform_object_with<E, F>(
+    object: &mut [u8],
+    options: &TransformOptions,
+    transformation: &F,
+) -> TransformResult<(), E>
+where
+    E: std::error::Error,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<Vec<Patch>, E> + ?Sized,
+{
+    transform_each(
+        object,
+        options,
+        &|bytes: &[u8], object: Object, context: &Context| {
+            transformation(bytes, object, context).map(|patches| (patches, ()))
+        },
+    )
+    .into_iter()
+    .try_for_each(|result| result.map(drop))
+}
+
+/// Apply a transformation to the objects in an archive or fat binary in parallel.
+///
+/// Archive members and fat binary slices occupy disjoint bytes, so each object is transformed
+/// independently on the rayon thread pool.  If more than one object fails, the error for the
+/// first is returned.
+#[cfg(feature = "rayon")]
+pub fn transform_object_parallel<E, F>(
+    object: &mut [u8],
+    options: &TransformOptions,
+    transformation: &F,
+) -> TransformResult<(), E>
+where
+    E: std::error::Error + Send,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<Vec<Patch>, E> + Sync + ?Sized,
+{
+    transform_each_parallel(
+        object,
+        options,
+        &|bytes: &[u8], object: Object, context: &Context| {
+            transformation(bytes, object, context).map(|patches| (patches, ()))
+        },
+    )
+    .into_iter()
+    .try_for_each(|result| result.map(drop))
+}
+
+/// Apply a function to each object in a binary or an archive of binaries, without modifying it.
+///
+/// Objects are skipped according to `options`.  Returns the context of each object that was
+/// not skipped, in the order they are stored, with the output of the inspection.
+pub fn inspect_object<T, E, F>(
+    object: &[u8],
+    options: &TransformOptions,
+    inspection: &F,
+) -> TransformResult<Vec<(Context, T)>, E>
+where
+    E: std::error::Error,
+    F: for<'a> Fn(&'a [u8], Object, &Context) -> std::result::Result<T, E> + ?Sized,
+{
+    let mut outputs = Vec::new();
+    for range in object_ranges(object)? {
+        let buf = object
+            .get(range.offset..range.offset + range.size)
+            .ok_or_else(|| {
+                Error::Malformed("object extends past the end of the file".to_string())
+            })?;
+        if options.skips(&range.context, buf) {
+            continue;
+        }
+        let output = parse_object(buf)
+            .map_err(TransformError::from)
+            .and_then(|parsed| {
+                inspection(buf, parsed, &range.context).map_err(TransformError::Transform)
+            });
+        let output = in_context(range.context.clone(), output)?;
+        outputs.push((range.context, output));
+    }
+    Ok(outputs)
+}
+
+fn repartition_macho(buf: &[u8]) -> Result<Vec<Patch>> {
+    let macho = MachO::parse(buf, 0)?;
+    crate::mach::dysymtab::repartition(buf, &macho)
 }
@@ -29,6 +29,15 @@ impl<T> Rooted<T> {
         Self { value, location }
     }
 
+    pub(crate) fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// The number of bytes available at this value's location.
+    pub fn size(&self) -> usize {
+        self.location.size
+    }
+
     /// Construct a patch that replaces this rooted value.
     pub fn patch_with<U>(&self, value: U) -> Result<Patch>
     where
@@ -52,14 +61,14 @@ impl<T> std::ops::Deref for Rooted<T> {
 }
 
 /// Represents a patch to an object.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Patch {
     offset: usize,
     data: Vec<u8>,
 }
 
 impl Patch {
-    fn from_ctx<T>(location: &Location, data: T) -> Result<Self>
+    pub(crate) fn from_ctx<T>(location: &Location, data: T) -> Result<Self>
     where
         T: TryIntoCtx<Ctx, [u8], Error = goblin::error::Error> + SizeWith<Ctx>,
     {
@@ -75,7 +84,7 @@ impl Patch {
         })
     }
 
-    fn from_bytes(location: &Location, data: &[u8]) -> Result<Self> {
+    pub(crate) fn from_bytes(location: &Location, data: &[u8]) -> Result<Self> {
         if data.len() > location.size {
             return Err(Error::PatchTooBig);
         }
@@ -85,8 +94,127 @@ impl Patch {
         })
     }
 
+    /// Construct a patch that replaces the bytes at byte offset `offset` with `data`.
+    pub(crate) fn at(offset: usize, data: Vec<u8>) -> Self {
+        Self { offset, data }
+    }
+
+    /// The range of bytes modified by the patch.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..(self.offset + self.data.len())
+    }
+
     /// Apply the patch to the bytes of an object.
     pub fn apply(&self, data: &mut [u8]) {
-        data[self.offset..(self.offset + self.data.len())].clone_from_slice(&self.data);
+        data[self.range()].clone_from_slice(&self.data);
+    }
+
+    /// Apply the patch to a portion of an object, beginning at byte offset `offset`.
+    ///
+    /// Only the bytes of the patch that fall within `data` are applied.
+    pub(crate) fn apply_at(&self, data: &mut [u8], offset: usize) {
+        let range = self.range();
+        let start = range.start.max(offset);
+        let end = range.end.min(offset + data.len());
+        if start < end {
+            data[start - offset..end - offset]
+                .clone_from_slice(&self.data[start - range.start..end - range.start]);
+        }
+    }
+}
+
+impl std::fmt::Display for Patch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let range = self.range();
+        write!(f, "{:#x}..{:#x} ", range.start, range.end)?;
+        // Patches to string tables are more readable as strings
+        let text = self
+            .data
+            .iter()
+            .rposition(|b| *b != 0)
+            .map(|end| &self.data[..=end])
+            .unwrap_or(&[]);
+        if text.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            write!(f, "\"{}\"", String::from_utf8_lossy(text))
+        } else {
+            for (i, b) in self.data.iter().enumerate() {
+                if i != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:02x}", b)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Check that no two patches modify the same bytes with different values.
+///
+/// Overlapping patches are permitted if they agree on the overlapping bytes.  ELF string tables
+/// commonly share suffixes between strings (for example, `foo` may be stored within `barfoo`), so
+/// renaming both strings consistently produces overlapping, but compatible, patches.
+pub fn check_conflicts(patches: &[Patch]) -> Result<()> {
+    let mut sorted = patches.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|patch| patch.offset);
+    for (index, first) in sorted.iter().enumerate() {
+        let first_range = first.range();
+        for second in sorted[index + 1..]
+            .iter()
+            .take_while(|second| second.offset < first_range.end)
+        {
+            let second_range = second.range();
+            let overlap = second_range.start..first_range.end.min(second_range.end);
+            let first_bytes =
+                &first.data[overlap.start - first_range.start..overlap.end - first_range.start];
+            let second_bytes =
+                &second.data[overlap.start - second_range.start..overlap.end - second_range.start];
+            if first_bytes != second_bytes {
+                return Err(Error::ConflictingPatches(
+                    (*first).clone(),
+                    (*second).clone(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_patches_that_agree_are_accepted() {
+        // Renaming `barfoo` to `bazqux` and `foo` within it to `qux`
+        let patches = [
+            Patch::at(8, b"bazqux\0".to_vec()),
+            Patch::at(11, b"qux\0".to_vec()),
+        ];
+        assert!(check_conflicts(&patches).is_ok());
+    }
+
+    #[test]
+    fn overlapping_patches_that_disagree_are_rejected() {
+        let patches = [
+            Patch::at(11, b"qux\0".to_vec()),
+            Patch::at(8, b"bazquz\0".to_vec()),
+            Patch::at(0, b"abc\0".to_vec()),
+        ];
+        match check_conflicts(&patches) {
+            Err(Error::ConflictingPatches(first, second)) => {
+                assert_eq!(first.range(), 8..15);
+                assert_eq!(second.range(), 11..15);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn adjacent_patches_do_not_conflict() {
+        let patches = [
+            Patch::at(4, b"abcd".to_vec()),
+            Patch::at(0, b"wxyz".to_vec()),
+        ];
+        assert!(check_conflicts(&patches).is_ok());
     }
 }
@@ -0,0 +1,112 @@
+//! Rename strings in place.
+
+use crate::error::{Error, Result};
+use crate::patch::{Patch, Rooted};
+
+/// Construct a patch that replaces a string in place, padding with NUL bytes as required.
+///
+/// The replacement must fit in the space occupied by the original string.
+pub fn rename_patch(name: &Rooted<&str>, new_name: &str) -> Result<Patch> {
+    if new_name.len() > name.size() {
+        return Err(Error::ReplaceString {
+            original: name.value.to_string(),
+            replacement: new_name.to_string(),
+        });
+    }
+    let mut new_name_bytes = new_name.as_bytes().to_vec();
+    new_name_bytes.resize(name.size(), 0);
+    name.patch_with_bytes(&new_name_bytes)
+}
+
+/// Rename strings in a string table, ensuring that no other strings are modified.
+///
+/// Each name is paired with its replacement, if it should be renamed.  `names` should contain
+/// every string in the string table, since linkers and assemblers commonly merge strings that
+/// share a suffix (for example, `bar` may be stored within `foobar`).  Renaming one of these
+/// strings in place would also rename the other, in which case an error naming every affected
+/// string is returned.
+pub fn rename_strings(bytes: &[u8], names: &[(&Rooted<&str>, Option<&str>)]) -> Result<Vec<Patch>> {
+    let mut patches = Vec::new();
+    for (name, new_name) in names {
+        if let Some(new_name) = new_name {
+            patches.push(rename_patch(name, new_name)?);
+        }
+    }
+
+    // Read back every string touched by a patch, including its NUL terminator
+    let mut affected: Vec<(String, String)> = Vec::new();
+    for (name, new_name) in names {
+        let location = name.location();
+        let range = location.offset..(location.offset + name.value.len() + 1).min(bytes.len());
+        let mut patched = None;
+        for patch in &patches {
+            let patch_range = patch.range();
+            if patch_range.start < range.end && range.start < patch_range.end {
+                let buf = patched.get_or_insert_with(|| bytes[range.clone()].to_vec());
+                patch.apply_at(buf, range.start);
+            }
+        }
+        if let Some(patched) = patched {
+            let end = patched
+                .iter()
+                .position(|b| *b == 0)
+                .unwrap_or(patched.len());
+            let result = String::from_utf8_lossy(&patched[..end]);
+            let expected = new_name.unwrap_or(name.value);
+            if result != expected
+                && !affected
+                    .iter()
+                    .any(|(affected, _)| affected.as_str() == name.value)
+            {
+                affected.push((name.value.to_string(), result.into_owned()));
+            }
+        }
+    }
+
+    if affected.is_empty() {
+        Ok(patches)
+    } else {
+        Err(Error::SharedString(affected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Location;
+    use goblin::container::Ctx;
+
+    const STRTAB: &[u8] = b"\0foobar\0";
+
+    fn string(offset: usize, value: &str) -> Rooted<&str> {
+        let location = Location {
+            offset,
+            size: value.len(),
+            ctx: Ctx::default(),
+        };
+        Rooted::new(location, value)
+    }
+
+    #[test]
+    fn renaming_a_shared_string_fails() {
+        let (foobar, bar) = (string(1, "foobar"), string(4, "bar"));
+        match rename_strings(STRTAB, &[(&foobar, None), (&bar, Some("baz"))]) {
+            Err(Error::SharedString(affected)) => {
+                assert_eq!(affected, vec![("foobar".to_string(), "foobaz".to_string())])
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn renaming_every_sharing_string_consistently_succeeds() {
+        let (foobar, bar) = (string(1, "foobar"), string(4, "bar"));
+        let patches =
+            rename_strings(STRTAB, &[(&foobar, Some("quxbaz")), (&bar, Some("baz"))]).unwrap();
+        let mut renamed = STRTAB.to_vec();
+        for patch in patches {
+            patch.apply(&mut renamed);
+        }
+        assert_eq!(renamed, b"\0quxbaz\0");
+    }
+}
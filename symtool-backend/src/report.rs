@@ -0,0 +1,142 @@
+//! Reports of the changes made by an [`Editor`](crate::editor::Editor).
+
+use crate::object::Context;
+use crate::symbol::Visibility;
+
+/// A change made to an object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A symbol was removed from the symbol table
+    StripSymbol { name: String },
+
+    /// The visibility of a symbol was set
+    SetVisibility {
+        name: String,
+        old: Visibility,
+        new: Visibility,
+    },
+
+    /// A symbol was renamed
+    RenameSymbol { old: String, new: String },
+
+    /// A section was renamed
+    RenameSection { old: String, new: String },
+
+    /// A Mach-O segment was renamed
+    RenameSegment { old: String, new: String },
+
+    /// An ELF symbol version was renamed
+    RenameVersion { old: String, new: String },
+
+    /// The name of an ELF shared object was set
+    SetSoname { old: String, new: String },
+
+    /// An ELF dependency was replaced
+    ReplaceNeeded { old: String, new: String },
+
+    /// The version of an ELF dynamic symbol was set, replacing its previous version, if any
+    SetSymbolVersion {
+        name: String,
+        old: Option<String>,
+        new: String,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::StripSymbol { name } => write!(f, "Strip symbol: {}", name),
+            Self::SetVisibility { name, new, .. } => write!(f, "Set visibility {}: {}", new, name),
+            Self::RenameSymbol { old, new } => write!(f, "Rename symbol: {} -> {}", old, new),
+            Self::RenameSection { old, new } => write!(f, "Rename section: {} -> {}", old, new),
+            Self::RenameSegment { old, new } => write!(f, "Rename segment: {} -> {}", old, new),
+            Self::RenameVersion { old, new } => write!(f, "Rename version: {} -> {}", old, new),
+            Self::SetSoname { old, new } => write!(f, "Set soname: {} -> {}", old, new),
+            Self::ReplaceNeeded { old, new } => write!(f, "Replace needed: {} -> {}", old, new),
+            Self::SetSymbolVersion { name, new, .. } => write!(f, "Set version {}: {}", new, name),
+        }
+    }
+}
+
+/// An editing rule, identified by its pattern or the name it renames.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    /// Set symbols matching the pattern to hidden visibility
+    Hide(String),
+
+    /// Set symbols matching the pattern to default visibility
+    Expose(String),
+
+    /// Remove symbols matching the pattern
+    Strip(String),
+
+    /// Rename the symbol
+    Rename(String),
+
+    /// Rename the section
+    RenameSection(String),
+
+    /// Rename the Mach-O segment
+    RenameSegment(String),
+
+    /// Rename the ELF symbol version
+    RenameVersion(String),
+
+    /// Set the version of the ELF dynamic symbol
+    SymbolVersion(String),
+
+    /// Set the name of the ELF shared object to the name
+    Soname(String),
+
+    /// Replace the ELF dependency
+    ReplaceNeeded(String),
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Hide(pattern) => write!(f, "hidden pattern \"{}\"", pattern),
+            Self::Expose(pattern) => write!(f, "default pattern \"{}\"", pattern),
+            Self::Strip(pattern) => write!(f, "strip pattern \"{}\"", pattern),
+            Self::Rename(name) => write!(f, "symbol rename \"{}\"", name),
+            Self::RenameSection(name) => write!(f, "section rename \"{}\"", name),
+            Self::RenameSegment(name) => write!(f, "segment rename \"{}\"", name),
+            Self::RenameVersion(name) => write!(f, "version rename \"{}\"", name),
+            Self::SymbolVersion(name) => write!(f, "symbol version \"{}\"", name),
+            Self::Soname(name) => write!(f, "soname \"{}\"", name),
+            Self::ReplaceNeeded(name) => write!(f, "needed replacement \"{}\"", name),
+        }
+    }
+}
+
+/// The changes made to an object.
+#[derive(Clone, Debug)]
+pub struct ObjectReport {
+    /// The location of the object within an archive or fat binary
+    pub context: Context,
+
+    /// The changes, in the order they were made
+    pub changes: Vec<Change>,
+}
+
+/// A report of the changes made by an [`Editor`](crate::editor::Editor).
+///
+/// Local symbols removed by [`strip_locals`](crate::editor::Editor::strip_locals) are not listed.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// The changes made to each edited object, in the order the objects are stored
+    pub objects: Vec<ObjectReport>,
+
+    /// Each rule with the number of times it matched a name
+    pub rules: Vec<(Rule, usize)>,
+}
+
+impl Report {
+    /// The rules that did not match any name.
+    pub fn unmatched(&self) -> impl Iterator<Item = &Rule> {
+        self.rules
+            .iter()
+            .filter(|(_, matches)| *matches == 0)
+            .map(|(rule, _)| rule)
+    }
+}
@@ -0,0 +1,463 @@
+//! Remove symbols from symbol tables.
+//!
+//! Symbols are removed by compacting the symbol table in place: the remaining symbols are moved
+//! down to fill the gaps, the vacated entries at the end of the table are zeroed, and the size of
+//! the table is reduced.  Every reference to a symbol index (relocations, section groups, and
+//! indirect symbols) is updated to match.  String tables and the rest of the binary are left
+//! untouched.
+
+use crate::error::{Error, Result};
+use crate::mach::dysymtab::{
+    find_dysymtab, patch_u32, relocation_offsets, relocation_symbol, with_relocation_symbol,
+    INDIRECT_SYMBOL_ABS, INDIRECT_SYMBOL_LOCAL,
+};
+use crate::object::Object;
+use crate::patch::{Location, Patch};
+use crate::symbol::Symbol;
+use goblin::container::Ctx;
+use goblin::elf::section_header::{
+    SectionHeader, SHT_GROUP, SHT_REL, SHT_RELA, SHT_SYMTAB, SHT_SYMTAB_SHNDX,
+};
+use goblin::elf::Elf;
+use goblin::mach::load_command::{CommandVariant, SymtabCommand, SIZEOF_SYMTAB_COMMAND};
+use goblin::mach::symbols::Nlist;
+use goblin::mach::MachO;
+use scroll::ctx::SizeWith;
+use scroll::{Pread, Pwrite};
+
+/// LLVM's address-significance table, which refers to symbols by index.
+const SHT_LLVM_ADDRSIG: u32 = 0x6fff_4c03;
+
+/// What to do with a symbol.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Keep the symbol
+    Keep,
+
+    /// Remove the symbol, failing if it is referenced by a relocation
+    Remove,
+
+    /// Remove the symbol, unless it is referenced by a relocation
+    RemoveUnreferenced,
+}
+
+/// The patches that remove symbols from a binary, and the symbols they remove.
+#[derive(Default)]
+pub struct Stripped<'a> {
+    /// The patches to apply
+    pub patches: Vec<Patch>,
+
+    /// The original index of each removed symbol, and the symbol, in the order they were stored
+    pub removed: Vec<(usize, Symbol<'a>)>,
+}
+
+impl<'a> Stripped<'a> {
+    fn new(patches: Vec<Patch>, symbols: Vec<Symbol<'a>>, keep: &[bool]) -> Self {
+        let removed = symbols
+            .into_iter()
+            .enumerate()
+            .zip(keep)
+            .filter(|(_, keep)| !**keep)
+            .map(|(symbol, _)| symbol)
+            .collect();
+        Self { patches, removed }
+    }
+}
+
+/// Decide which symbols to keep.
+///
+/// `symbols` yields the name of each symbol and the action to take.  Returns `None` if every
+/// symbol is kept.
+fn select<'a>(
+    symbols: impl Iterator<Item = (Option<&'a str>, Action)>,
+    referenced: &[bool],
+) -> Result<Option<Vec<bool>>> {
+    let mut keep = Vec::new();
+    for (index, (name, action)) in symbols.enumerate() {
+        let is_referenced = referenced.get(index).copied().unwrap_or(false);
+        keep.push(match action {
+            Action::Keep => true,
+            Action::Remove if is_referenced => {
+                return Err(Error::ReferencedSymbol(
+                    name.map(str::to_string)
+                        .unwrap_or_else(|| format!("#{}", index)),
+                ))
+            }
+            Action::Remove => false,
+            Action::RemoveUnreferenced => is_referenced,
+        });
+    }
+    if keep.iter().all(|keep| *keep) {
+        Ok(None)
+    } else {
+        Ok(Some(keep))
+    }
+}
+
+/// Map each kept entry to its new index.
+fn remap(keep: &[bool]) -> Vec<Option<usize>> {
+    let mut next = 0;
+    keep.iter()
+        .map(|keep| {
+            if *keep {
+                next += 1;
+                Some(next - 1)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Produce patches that compact a table of fixed-size entries, zeroing the vacated entries.
+fn compact_table(
+    bytes: &[u8],
+    offset: usize,
+    entsize: usize,
+    keep: &[bool],
+    ctx: Ctx,
+) -> Result<Vec<Patch>> {
+    let table = bytes
+        .get(offset..offset + entsize * keep.len())
+        .ok_or_else(|| Error::Malformed("symbol table out of bounds".to_string()))?;
+    let mut patches = Vec::new();
+    let mut next = 0;
+    for (index, keep) in keep.iter().enumerate() {
+        if !keep {
+            continue;
+        }
+        if next != index {
+            let location = Location {
+                offset: offset + next * entsize,
+                size: entsize,
+                ctx,
+            };
+            let entry = &table[index * entsize..(index + 1) * entsize];
+            patches.push(Patch::from_bytes(&location, entry)?);
+        }
+        next += 1;
+    }
+    let location = Location {
+        offset: offset + next * entsize,
+        size: entsize * (keep.len() - next),
+        ctx,
+    };
+    patches.push(Patch::from_bytes(&location, &vec![0; location.size])?);
+    Ok(patches)
+}
+
+/// Extract the symbol index referenced by an ELF relocation's `r_info`.
+fn elf_relocation_symbol(r_info: u64, ctx: Ctx) -> usize {
+    if ctx.is_big() {
+        (r_info >> 32) as usize
+    } else {
+        (r_info >> 8) as usize
+    }
+}
+
+/// Replace the symbol index referenced by an ELF relocation's `r_info`.
+fn elf_with_relocation_symbol(r_info: u64, ctx: Ctx, symbol: usize) -> u64 {
+    if ctx.is_big() {
+        (r_info & 0xffff_ffff) | ((symbol as u64) << 32)
+    } else {
+        (r_info & 0xff) | ((symbol as u64) << 8)
+    }
+}
+
+/// Produce patches that remove symbols from an ELF binary's static symbol table.
+///
+/// `action` is called with each symbol, and decides whether it is removed.  The null symbol at
+/// index 0 is always kept.  The dynamic symbol table is never modified.
+pub fn strip_elf<'a, F>(bytes: &'a [u8], elf: &Elf, mut action: F) -> Result<Stripped<'a>>
+where
+    F: FnMut(&Symbol) -> Action,
+{
+    let ctx = crate::elf::context_from_elf(elf);
+    let headers = crate::elf::SectionIter::from_elf(bytes, elf)?
+        .map(|header| header.map(|(_, header)| header))
+        .collect::<Result<Vec<_>>>()?;
+    let symtab_index = match headers
+        .iter()
+        .position(|header| header.sh_type == SHT_SYMTAB)
+    {
+        Some(index) => index,
+        None => return Ok(Stripped::default()),
+    };
+    let symtab = &headers[symtab_index];
+    let symbols =
+        crate::elf::SymtabIter::from_section_header(bytes, symtab, &elf.section_headers, ctx)?
+            .map(|symbol| symbol.map(|(name, sym)| Symbol::Elf { name, sym }))
+            .collect::<Result<Vec<_>>>()?;
+
+    // Find every reference to a symbol
+    let mut referenced = vec![false; symbols.len()];
+    let mut relocations = Vec::new();
+    let mut has_addrsig = false;
+    let mut reference = |index: usize| {
+        *referenced
+            .get_mut(index)
+            .ok_or_else(|| Error::Malformed(format!("symbol index {} out of range", index)))? =
+            true;
+        Ok::<(), Error>(())
+    };
+    for header in headers
+        .iter()
+        .filter(|h| h.sh_link as usize == symtab_index)
+    {
+        match header.sh_type {
+            SHT_REL | SHT_RELA => {
+                let entsize = header.sh_entsize as usize;
+                if entsize < 2 * ctx.size() {
+                    return Err(Error::Malformed("sh_entsize too small".to_string()));
+                }
+                for index in 0..header.sh_size as usize / entsize {
+                    let offset = header.sh_offset as usize + index * entsize + ctx.size();
+                    let r_info = if ctx.is_big() {
+                        bytes.pread_with::<u64>(offset, ctx.le)?
+                    } else {
+                        bytes.pread_with::<u32>(offset, ctx.le)?.into()
+                    };
+                    reference(elf_relocation_symbol(r_info, ctx))?;
+                    relocations.push((offset, r_info));
+                }
+            }
+            SHT_GROUP => reference(header.sh_info as usize)?,
+            SHT_LLVM_ADDRSIG => has_addrsig = true,
+            _ => {}
+        }
+    }
+
+    let names = symbols.iter().enumerate().map(|(index, symbol)| {
+        let name = symbol.name().map(|name| name.value);
+        if index == 0 {
+            (name, Action::Keep)
+        } else {
+            (name, action(symbol))
+        }
+    });
+    let keep = match select(names, &referenced)? {
+        Some(keep) => keep,
+        None => return Ok(Stripped::default()),
+    };
+    if has_addrsig {
+        return Err(Error::Unsupported(
+            "stripping symbols from binaries with an address-significance table".to_string(),
+        ));
+    }
+    let remap = remap(&keep);
+    let remapped = |index: usize| remap[index].expect("referenced symbol removed");
+
+    // Compact the symbol table, and its extended section indices
+    let mut patches = compact_table(
+        bytes,
+        symtab.sh_offset as usize,
+        symtab.sh_entsize as usize,
+        &keep,
+        ctx,
+    )?;
+    let count = keep.iter().filter(|keep| **keep).count();
+    let locals = keep
+        .iter()
+        .take(symtab.sh_info as usize)
+        .filter(|keep| **keep)
+        .count();
+    patches.push(symtab.patch_with(SectionHeader {
+        sh_size: (count * symtab.sh_entsize as usize) as u64,
+        sh_info: locals as u32,
+        ..**symtab
+    })?);
+    for header in headers
+        .iter()
+        .filter(|h| h.sh_link as usize == symtab_index)
+    {
+        match header.sh_type {
+            SHT_SYMTAB_SHNDX => {
+                patches.extend(compact_table(
+                    bytes,
+                    header.sh_offset as usize,
+                    4,
+                    &keep,
+                    ctx,
+                )?);
+                patches.push(header.patch_with(SectionHeader {
+                    sh_size: (count * 4) as u64,
+                    ..**header
+                })?);
+            }
+            SHT_GROUP if remapped(header.sh_info as usize) != header.sh_info as usize => {
+                patches.push(header.patch_with(SectionHeader {
+                    sh_info: remapped(header.sh_info as usize) as u32,
+                    ..**header
+                })?);
+            }
+            _ => {}
+        }
+    }
+
+    // Renumber relocations
+    for (offset, r_info) in relocations {
+        let symbol = elf_relocation_symbol(r_info, ctx);
+        if remapped(symbol) == symbol {
+            continue;
+        }
+        let r_info = elf_with_relocation_symbol(r_info, ctx, remapped(symbol));
+        let mut buf = [0u8; 8];
+        if ctx.is_big() {
+            buf.pwrite_with(r_info, 0, ctx.le)?;
+        } else {
+            buf.pwrite_with(r_info as u32, 0, ctx.le)?;
+        }
+        let location = Location {
+            offset,
+            size: ctx.size(),
+            ctx,
+        };
+        patches.push(Patch::from_bytes(&location, &buf[..ctx.size()])?);
+    }
+
+    Ok(Stripped::new(patches, symbols, &keep))
+}
+
+/// Produce patches that remove symbols from a Mach-O binary's symbol table.
+///
+/// `action` is called with each symbol, and decides whether it is removed.  Symbols referenced by
+/// the indirect symbol table are treated as referenced by a relocation.
+pub fn strip_mach<'a, F>(bytes: &'a [u8], mach: &MachO, mut action: F) -> Result<Stripped<'a>>
+where
+    F: FnMut(&Symbol) -> Action,
+{
+    let ctx = crate::mach::context_from_macho(mach);
+    let (symtab_offset, symtab) = match mach.load_commands.iter().find_map(|command| match command
+        .command
+    {
+        CommandVariant::Symtab(symtab) => Some((command.offset, symtab)),
+        _ => None,
+    }) {
+        Some(symtab) => symtab,
+        None => return Ok(Stripped::default()),
+    };
+    let symbols = match crate::mach::SymtabIter::from_mach(bytes, mach) {
+        Some(iter) => iter
+            .map(|symbol| symbol.map(|(name, nlist)| Symbol::MachO { name, nlist }))
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let dysymtab = find_dysymtab(mach);
+
+    // Find every reference to a symbol
+    let mut referenced = vec![false; symbols.len()];
+    let mut reference = |index: u32| {
+        *referenced
+            .get_mut(index as usize)
+            .ok_or_else(|| Error::Malformed(format!("symbol index {} out of range", index)))? =
+            true;
+        Ok::<(), Error>(())
+    };
+    let relocations = relocation_offsets(bytes, mach, dysymtab.as_ref().map(|(_, d)| d))?;
+    for offset in &relocations {
+        let r_info: u32 = bytes.pread_with(offset + 4, ctx.le)?;
+        if let Some(symbol) = relocation_symbol(r_info, ctx.le) {
+            reference(symbol)?;
+        }
+    }
+    let mut indirect = Vec::new();
+    if let Some((_, dysymtab)) = &dysymtab {
+        for index in 0..dysymtab.nindirectsyms as usize {
+            let offset = dysymtab.indirectsymoff as usize + index * 4;
+            let symbol: u32 = bytes.pread_with(offset, ctx.le)?;
+            if symbol & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) == 0 {
+                reference(symbol)?;
+                indirect.push((offset, symbol));
+            }
+        }
+    }
+
+    let names = symbols
+        .iter()
+        .map(|symbol| (symbol.name().map(|name| name.value), action(symbol)));
+    let keep = match select(names, &referenced)? {
+        Some(keep) => keep,
+        None => return Ok(Stripped::default()),
+    };
+    let remap = remap(&keep);
+    let remapped = |index: u32| remap[index as usize].expect("referenced symbol removed") as u32;
+
+    // Compact the symbol table
+    let mut patches = compact_table(
+        bytes,
+        symtab.symoff as usize,
+        Nlist::size_with(&ctx),
+        &keep,
+        ctx,
+    )?;
+    let kept_before = |index: u32| keep.iter().take(index as usize).filter(|k| **k).count() as u32;
+    let mut command = [0u8; SIZEOF_SYMTAB_COMMAND];
+    command.pwrite_with(
+        SymtabCommand {
+            nsyms: kept_before(symtab.nsyms),
+            ..symtab
+        },
+        0,
+        ctx.le,
+    )?;
+    patches.push(Patch::from_bytes(
+        &Location {
+            offset: symtab_offset,
+            size: command.len(),
+            ctx,
+        },
+        &command,
+    )?);
+
+    // Shrink the partitions, which remain in order
+    if let Some((dysymtab_offset, dysymtab)) = dysymtab {
+        if dysymtab.ntoc != 0 || dysymtab.nmodtab != 0 || dysymtab.nextrefsyms != 0 {
+            return Err(Error::Unsupported(
+                "stripping symbols from binaries with a table of contents, module table, or referenced symbol table".to_string(),
+            ));
+        }
+        let partitions = [
+            (8, dysymtab.ilocalsym, dysymtab.nlocalsym),
+            (16, dysymtab.iextdefsym, dysymtab.nextdefsym),
+            (24, dysymtab.iundefsym, dysymtab.nundefsym),
+        ];
+        for (field, start, count) in partitions.iter() {
+            let new_start = kept_before(*start);
+            let new_count = kept_before(start + count) - new_start;
+            let offset = dysymtab_offset + field;
+            patches.push(patch_u32(offset, new_start, ctx)?);
+            patches.push(patch_u32(offset + 4, new_count, ctx)?);
+        }
+    }
+
+    // Renumber the indirect symbol table and relocations
+    for (offset, symbol) in indirect {
+        if remapped(symbol) != symbol {
+            patches.push(patch_u32(offset, remapped(symbol), ctx)?);
+        }
+    }
+    for offset in relocations {
+        let r_info: u32 = bytes.pread_with(offset + 4, ctx.le)?;
+        if let Some(symbol) = relocation_symbol(r_info, ctx.le) {
+            if remapped(symbol) != symbol {
+                let r_info = with_relocation_symbol(r_info, ctx.le, remapped(symbol));
+                patches.push(patch_u32(offset + 4, r_info, ctx)?);
+            }
+        }
+    }
+
+    Ok(Stripped::new(patches, symbols, &keep))
+}
+
+/// Produce patches that remove symbols from an object's symbol table.
+///
+/// See [`strip_elf`] and [`strip_mach`] for details.
+pub fn strip<'a, F>(bytes: &'a [u8], object: &Object, action: F) -> Result<Stripped<'a>>
+where
+    F: FnMut(&Symbol) -> Action,
+{
+    match object {
+        Object::Elf(elf) => strip_elf(bytes, elf, action),
+        Object::MachO(mach) => strip_mach(bytes, mach, action),
+    }
+}
@@ -0,0 +1,308 @@
+//! Format-agnostic access to symbols.
+
+use crate::error::{Error, Result};
+use crate::object::Object;
+use crate::patch::{Patch, Rooted};
+use goblin::elf::section_header::{SHN_LORESERVE, SHN_UNDEF};
+use goblin::elf::sym::{
+    Sym, STB_GLOBAL, STB_LOCAL, STB_WEAK, STT_COMMON, STT_FILE, STT_FUNC, STT_NOTYPE, STT_OBJECT,
+    STT_SECTION, STT_TLS, STV_DEFAULT, STV_HIDDEN, STV_INTERNAL, STV_PROTECTED,
+};
+use goblin::mach::symbols::{Nlist, N_PEXT, N_SECT, N_STAB, N_TYPE, N_WEAK_DEF, N_WEAK_REF};
+
+/// The binding of a symbol, which determines its visibility to the linker.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// Visible only within the object
+    Local,
+
+    /// Visible to other objects
+    Global,
+
+    /// Visible to other objects, but may be overridden or left undefined
+    Weak,
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Global => write!(f, "global"),
+            Self::Weak => write!(f, "weak"),
+        }
+    }
+}
+
+/// The kind of entity a symbol refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// The symbol's kind is not recorded (Mach-O symbols never record a kind)
+    Unspecified,
+
+    /// A function or other executable code
+    Function,
+
+    /// A variable or other data
+    Object,
+
+    /// A section
+    Section,
+
+    /// A source file
+    File,
+
+    /// A debugging entry, such as a Mach-O STABS entry
+    Debug,
+
+    /// Any other kind
+    Other,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unspecified => write!(f, "notype"),
+            Self::Function => write!(f, "function"),
+            Self::Object => write!(f, "object"),
+            Self::Section => write!(f, "section"),
+            Self::File => write!(f, "file"),
+            Self::Debug => write!(f, "debug"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// The visibility of a symbol outside of the linked binary.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// Visible according to the symbol's binding
+    Default,
+
+    /// Not visible outside of the linked binary
+    Hidden,
+
+    /// Visible, but references within the linked binary cannot be preempted (ELF only)
+    Protected,
+
+    /// Hidden, with additional processor-specific semantics (ELF only)
+    Internal,
+}
+
+impl std::fmt::Display for Visibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Hidden => write!(f, "hidden"),
+            Self::Protected => write!(f, "protected"),
+            Self::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+/// A symbol in an ELF or Mach-O symbol table.
+pub enum Symbol<'a> {
+    Elf {
+        name: Option<Rooted<&'a str>>,
+        sym: Rooted<Sym>,
+    },
+    MachO {
+        name: Option<Rooted<&'a str>>,
+        nlist: Rooted<Nlist>,
+    },
+}
+
+impl<'a> Symbol<'a> {
+    /// The name of the symbol, if it has one.
+    pub fn name(&self) -> Option<&Rooted<&'a str>> {
+        match self {
+            Self::Elf { name, .. } | Self::MachO { name, .. } => name.as_ref(),
+        }
+    }
+
+    /// The binding of the symbol.
+    pub fn binding(&self) -> Binding {
+        match self {
+            Self::Elf { sym, .. } => match sym.st_bind() {
+                STB_LOCAL => Binding::Local,
+                STB_WEAK => Binding::Weak,
+                _ => Binding::Global,
+            },
+            Self::MachO { nlist, .. } => {
+                if nlist.n_type & N_STAB != 0 || !nlist.is_global() {
+                    Binding::Local
+                } else if nlist.n_desc & (N_WEAK_DEF | N_WEAK_REF) != 0 {
+                    Binding::Weak
+                } else {
+                    Binding::Global
+                }
+            }
+        }
+    }
+
+    /// The kind of entity the symbol refers to.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Elf { sym, .. } => match sym.st_type() {
+                STT_NOTYPE => Kind::Unspecified,
+                STT_FUNC => Kind::Function,
+                STT_OBJECT | STT_COMMON | STT_TLS => Kind::Object,
+                STT_SECTION => Kind::Section,
+                STT_FILE => Kind::File,
+                _ => Kind::Other,
+            },
+            Self::MachO { nlist, .. } => {
+                if nlist.n_type & N_STAB != 0 {
+                    Kind::Debug
+                } else {
+                    Kind::Unspecified
+                }
+            }
+        }
+    }
+
+    /// The visibility of the symbol.
+    pub fn visibility(&self) -> Visibility {
+        match self {
+            Self::Elf { sym, .. } => match sym.st_visibility() {
+                STV_HIDDEN => Visibility::Hidden,
+                STV_PROTECTED => Visibility::Protected,
+                STV_INTERNAL => Visibility::Internal,
+                _ => Visibility::Default,
+            },
+            Self::MachO { nlist, .. } => {
+                if nlist.n_type & N_PEXT != 0 {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Default
+                }
+            }
+        }
+    }
+
+    /// Returns true if the symbol is defined in this object.
+    ///
+    /// Common symbols are considered defined in ELF objects, but undefined in Mach-O objects.
+    pub fn is_defined(&self) -> bool {
+        match self {
+            Self::Elf { sym, .. } => sym.st_shndx as u32 != SHN_UNDEF,
+            Self::MachO { nlist, .. } => nlist.n_type & N_STAB == 0 && !nlist.is_undefined(),
+        }
+    }
+
+    /// The index of the section containing the symbol, if it is defined in a section.
+    ///
+    /// ELF section indices start at 0, and Mach-O section ordinals start at 1.  ELF symbols with
+    /// extended section indices (`SHN_XINDEX`) are not resolved, and return `None`.
+    pub fn section(&self) -> Option<usize> {
+        match self {
+            Self::Elf { sym, .. } => {
+                let shndx = sym.st_shndx as u32;
+                if shndx != SHN_UNDEF && shndx < SHN_LORESERVE {
+                    Some(shndx as usize)
+                } else {
+                    None
+                }
+            }
+            Self::MachO { nlist, .. } => {
+                if nlist.n_type & N_STAB == 0 && nlist.n_type & N_TYPE == N_SECT {
+                    Some(nlist.n_sect)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Returns true if the symbol's name is the name of a function or variable.
+    ///
+    /// Mach-O STABS entries that describe a function or variable share its name, but other STABS
+    /// entries (such as source and object file paths) do not, and should not be renamed with it.
+    pub fn has_symbol_name(&self) -> bool {
+        match self {
+            Self::Elf { .. } => true,
+            Self::MachO { nlist, .. } => {
+                nlist.n_type & N_STAB == 0 || crate::mach::is_symbol_stab(nlist)
+            }
+        }
+    }
+
+    /// Construct a patch that changes the visibility of the symbol.
+    ///
+    /// Only global and weak symbols have a visibility; `None` is returned for other symbols, as
+    /// well as ELF symbols without a type (such as undefined symbols).  Mach-O symbols only
+    /// support default and hidden visibility.
+    pub fn set_visibility(&self, visibility: Visibility) -> Result<Option<Patch>> {
+        match self {
+            Self::Elf { sym, .. } => {
+                if (sym.st_bind() != STB_GLOBAL && sym.st_bind() != STB_WEAK)
+                    || sym.st_type() == STT_NOTYPE
+                {
+                    return Ok(None);
+                }
+                let st_visibility = match visibility {
+                    Visibility::Default => STV_DEFAULT,
+                    Visibility::Hidden => STV_HIDDEN,
+                    Visibility::Protected => STV_PROTECTED,
+                    Visibility::Internal => STV_INTERNAL,
+                };
+                let new_sym = Sym {
+                    st_other: (sym.st_other & 0xfc) | st_visibility,
+                    ..**sym
+                };
+                Ok(Some(sym.patch_with(new_sym)?))
+            }
+            Self::MachO { nlist, .. } => {
+                if !nlist.is_global() || nlist.n_type & N_STAB != 0 {
+                    return Ok(None);
+                }
+                let n_type = match visibility {
+                    Visibility::Default => nlist.n_type & !N_PEXT,
+                    Visibility::Hidden => nlist.n_type | N_PEXT,
+                    _ => {
+                        return Err(Error::Unsupported(format!(
+                            "{} visibility in Mach-O binaries",
+                            visibility
+                        )))
+                    }
+                };
+                let new_nlist = Nlist {
+                    n_type,
+                    ..(**nlist).clone()
+                };
+                Ok(Some(nlist.patch_with(new_nlist)?))
+            }
+        }
+    }
+
+    /// Construct a patch that renames the symbol in place.
+    ///
+    /// The new name must fit in the space occupied by the original.  Names in string tables may
+    /// share storage with other strings, so prefer [`rename_strings`](crate::rename::rename_strings)
+    /// when renaming more than one string.
+    pub fn rename(&self, new_name: &str) -> Result<Patch> {
+        match self.name() {
+            Some(name) => crate::rename::rename_patch(name, new_name),
+            None => Err(Error::Unsupported("renaming an unnamed symbol".to_string())),
+        }
+    }
+}
+
+/// Read every symbol in an object's symbol table.
+///
+/// For ELF objects, this is the static symbol table (`.symtab`).
+pub fn symbols<'a>(bytes: &'a [u8], object: &Object) -> Result<Vec<Symbol<'a>>> {
+    match object {
+        Object::Elf(elf) => match crate::elf::SymtabIter::symtab_from_elf(bytes, elf)? {
+            Some(iter) => iter
+                .map(|symbol| symbol.map(|(name, sym)| Symbol::Elf { name, sym }))
+                .collect(),
+            None => Ok(Vec::new()),
+        },
+        Object::MachO(mach) => match crate::mach::SymtabIter::from_mach(bytes, mach) {
+            Some(iter) => iter
+                .map(|symbol| symbol.map(|(name, nlist)| Symbol::MachO { name, nlist }))
+                .collect(),
+            None => Ok(Vec::new()),
+        },
+    }
+}
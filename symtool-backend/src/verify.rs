@@ -0,0 +1,670 @@
+//! Verify that a transformation only modified symbol metadata.
+//!
+//! Patches are copied into an object without any knowledge of its structure, so a faulty
+//! transformation can silently corrupt the output.  Verification compares the original and
+//! modified binaries field by field, ensuring that symbols are unchanged other than their names
+//! and visibility, and that every modified byte belongs to a field symtool is expected to edit.
+
+use crate::archive::metadata_ranges;
+use crate::error::{Error, Result};
+use crate::mach::dysymtab::{
+    find_dysymtab, is_partitioned, relocation_offsets, relocation_symbol, INDIRECT_SYMBOL_ABS,
+    INDIRECT_SYMBOL_LOCAL,
+};
+use crate::object::{is_known_object, object_ranges};
+use crate::patch::Rooted;
+use crate::symbol::Symbol;
+use goblin::container::Container;
+use goblin::elf::section_header::{SHT_DYNSYM, SHT_SYMTAB};
+use goblin::elf::Elf;
+use goblin::mach::symbols::{Nlist, N_PEXT};
+use goblin::mach::MachO;
+use scroll::Pread;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// The maximum number of bytes printed when describing an unexpected change.
+const MAX_PRINTED_BYTES: usize = 16;
+
+/// The location of a rooted value as a range of bytes, relative to the start of the binary.
+fn range_of<T>(rooted: &Rooted<T>, base: usize) -> Range<usize> {
+    let location = rooted.location();
+    base + location.offset..base + location.offset + location.size
+}
+
+/// Find every range of bytes that differs between two equally sized binaries.
+fn changed_ranges(original: &[u8], modified: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (index, (a, b)) in original.iter().zip(modified.iter()).enumerate() {
+        if a != b {
+            match ranges.last_mut() {
+                Some(range) if range.end == index => range.end += 1,
+                _ => ranges.push(index..index + 1),
+            }
+        }
+    }
+    ranges
+}
+
+/// Find the parts of `range` that are not covered by the sorted, merged `allowed` ranges.
+fn uncovered(range: &Range<usize>, allowed: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut uncovered = Vec::new();
+    let mut start = range.start;
+    for allowed in allowed {
+        if allowed.end <= start {
+            continue;
+        }
+        if allowed.start >= range.end {
+            break;
+        }
+        if allowed.start > start {
+            uncovered.push(start..allowed.start);
+        }
+        start = allowed.end;
+        if start >= range.end {
+            break;
+        }
+    }
+    if start < range.end {
+        uncovered.push(start..range.end);
+    }
+    uncovered
+}
+
+/// Sort ranges and merge those that overlap or touch.
+pub(crate) fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Find the ranges covered by both of the sorted, merged `a` and `b` ranges.
+fn intersect(a: &[Range<usize>], b: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut intersection = Vec::new();
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+        let start = x.start.max(y.start);
+        let end = x.end.min(y.end);
+        if start < end {
+            intersection.push(start..end);
+        }
+        if x.end <= y.end {
+            a.next();
+        } else {
+            b.next();
+        }
+    }
+    intersection
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = bytes
+        .iter()
+        .take(MAX_PRINTED_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > MAX_PRINTED_BYTES {
+        hex.push_str(" ...");
+    }
+    hex
+}
+
+/// Options for verifying a transformation.
+#[derive(Clone, Debug, Default)]
+pub struct VerifyOptions {
+    /// Allow names in DWARF debug information to change, as with
+    /// [`Editor::rename_dwarf`](crate::editor::Editor::rename_dwarf).  Compressed debug
+    /// information is never renamed, so it may not change
+    pub rename_dwarf: bool,
+
+    /// The ranges of bytes the transformation intended to modify, if known.  Changes are then
+    /// only allowed to fields that may change and that lie within these ranges
+    pub intended: Option<Vec<Range<usize>>>,
+}
+
+/// Verify that `modified` differs from `original` only in symbol names and visibility.
+///
+/// Equivalent to [`verify_with`] using the default [`VerifyOptions`].
+pub fn verify(original: &[u8], modified: &[u8]) -> Result<()> {
+    verify_with(original, modified, &VerifyOptions::default())
+}
+
+/// Verify that `modified` differs from `original` only in symbol names and visibility.
+///
+/// Both binaries may be objects or archives of objects.  Symbol, section, and segment names may
+/// change, as may symbol visibility, and names in debug information if enabled by `options`.
+/// ELF symbol version names may change, as may the versions assigned to dynamic symbols and the
+/// names of shared objects and their dependencies.  Mach-O symbol tables may also be reordered to
+/// maintain the `LC_DYSYMTAB` partitions.  The timestamps, owners, and modes of archive members may change.
+/// Every unexpected difference is described in the returned error.
+pub fn verify_with(original: &[u8], modified: &[u8], options: &VerifyOptions) -> Result<()> {
+    if original.len() != modified.len() {
+        return Err(Error::Verification(vec![format!(
+            "size changed from {} to {} bytes",
+            original.len(),
+            modified.len()
+        )]));
+    }
+
+    let mut mismatches = Vec::new();
+
+    // Archive member timestamps, owners, and modes may be normalized
+    let mut allowed = metadata_ranges(original)?;
+    for object in object_ranges(original)? {
+        let range = object.offset..object.offset + object.size;
+
+        // Archive members and fat binary slices that aren't objects are never edited, so any
+        // change is unexpected
+        if object.context.is_nested() && !is_known_object(&original[range.clone()]) {
+            continue;
+        }
+
+        let mut object_mismatches = Vec::new();
+        verify_object(
+            &original[range.clone()],
+            &modified[range],
+            object.offset,
+            options,
+            &mut allowed,
+            &mut object_mismatches,
+        )
+        .map_err(|e| {
+            if object.context.is_nested() {
+                Error::Nested(object.context.clone(), Box::new(e))
+            } else {
+                e
+            }
+        })?;
+        if object.context.is_nested() {
+            mismatches.extend(
+                object_mismatches
+                    .into_iter()
+                    .map(|m| format!("{}: {}", object.context, m)),
+            );
+        } else {
+            mismatches.extend(object_mismatches);
+        }
+    }
+
+    let mut allowed = merge_ranges(allowed);
+    if let Some(intended) = &options.intended {
+        allowed = intersect(&allowed, &merge_ranges(intended.clone()));
+    }
+    for range in changed_ranges(original, modified) {
+        for range in uncovered(&range, &allowed) {
+            mismatches.push(format!(
+                "unexpected change at {:#x}..{:#x}: {} -> {}",
+                range.start,
+                range.end,
+                hex(&original[range.clone()]),
+                hex(&modified[range.clone()])
+            ));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Verification(mismatches))
+    }
+}
+
+/// Verify that `stripped` differs from `original` only by the removal of the `removed` symbols,
+/// as returned by [`strip`](crate::strip::strip).
+///
+/// Both binaries must be objects, rather than archives.  Every other symbol must be unchanged and
+/// in the same order.  Only symbol tables are compared, since stripping also rewrites relocations
+/// and the other references to symbol indices.
+pub fn verify_strip(original: &[u8], stripped: &[u8], removed: &[(usize, Symbol)]) -> Result<()> {
+    let object = crate::object::parse_object(original)?;
+    let symbols = crate::symbol::symbols(original, &object)?;
+    let stripped_symbols = match crate::object::parse_object(stripped)
+        .and_then(|object| crate::symbol::symbols(stripped, &object))
+    {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            return Err(Error::Verification(vec![format!(
+                "modified symbol table could not be read: {}",
+                e
+            )]))
+        }
+    };
+
+    let removed: HashSet<_> = removed
+        .iter()
+        .map(|(_, symbol)| symbol_offset(symbol))
+        .collect();
+    let kept: Vec<_> = symbols
+        .iter()
+        .filter(|symbol| !removed.contains(&symbol_offset(symbol)))
+        .collect();
+    let mut mismatches = Vec::new();
+    if symbols.len() - kept.len() != removed.len() {
+        mismatches.push("removed symbols not found in the original symbol table".to_string());
+    }
+    if kept.len() != stripped_symbols.len() {
+        mismatches.push(format!(
+            "expected {} symbols after stripping, found {}",
+            kept.len(),
+            stripped_symbols.len()
+        ));
+    }
+    for (index, (symbol, stripped_symbol)) in kept.iter().zip(&stripped_symbols).enumerate() {
+        if !same_symbol(symbol, stripped_symbol) {
+            mismatches.push(match symbol.name() {
+                Some(name) => format!("symbol '{}' changed by stripping", name.value),
+                None => format!("symbol {} changed by stripping", index),
+            });
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Verification(mismatches))
+    }
+}
+
+/// The offset of a symbol table entry.
+fn symbol_offset(symbol: &Symbol) -> usize {
+    match symbol {
+        Symbol::Elf { sym, .. } => sym.location().offset,
+        Symbol::MachO { nlist, .. } => nlist.location().offset,
+    }
+}
+
+/// Returns true if two symbols have the same name and fields.
+fn same_symbol(a: &Symbol, b: &Symbol) -> bool {
+    a.name().map(|name| name.value) == b.name().map(|name| name.value)
+        && match (a, b) {
+            (Symbol::Elf { sym: a, .. }, Symbol::Elf { sym: b, .. }) => **a == **b,
+            (Symbol::MachO { nlist: a, .. }, Symbol::MachO { nlist: b, .. }) => {
+                nlist_key(a) == nlist_key(b)
+            }
+            _ => false,
+        }
+}
+
+fn verify_object(
+    original: &[u8],
+    modified: &[u8],
+    base: usize,
+    options: &VerifyOptions,
+    allowed: &mut Vec<Range<usize>>,
+    mismatches: &mut Vec<String>,
+) -> Result<()> {
+    let object = goblin::Object::parse(original)?;
+
+    // Names in debug information may change
+    if options.rename_dwarf {
+        let names = match &object {
+            goblin::Object::Elf(elf) => crate::dwarf::uncompressed_names_from_elf(original, elf)?,
+            goblin::Object::Mach(goblin::mach::Mach::Binary(mach)) => {
+                crate::dwarf::names_from_mach(original, mach)?
+            }
+            _ => Vec::new(),
+        };
+        allowed.extend(names.iter().map(|name| range_of(name, base)));
+    }
+
+    match object {
+        goblin::Object::Elf(elf) => match Elf::parse(modified) {
+            Ok(modified_elf) => verify_elf(
+                original,
+                modified,
+                &elf,
+                &modified_elf,
+                base,
+                allowed,
+                mismatches,
+            ),
+            Err(e) => {
+                mismatches.push(format!("modified object could not be parsed: {}", e));
+                Ok(())
+            }
+        },
+        goblin::Object::Mach(goblin::mach::Mach::Binary(mach)) => match MachO::parse(modified, 0) {
+            Ok(modified_mach) => verify_mach(
+                original,
+                modified,
+                &mach,
+                &modified_mach,
+                base,
+                allowed,
+                mismatches,
+            ),
+            Err(e) => {
+                mismatches.push(format!("modified object could not be parsed: {}", e));
+                Ok(())
+            }
+        },
+        _ => Err(Error::UnknownObject),
+    }
+}
+
+fn verify_elf(
+    original: &[u8],
+    modified: &[u8],
+    elf: &Elf,
+    modified_elf: &Elf,
+    base: usize,
+    allowed: &mut Vec<Range<usize>>,
+    mismatches: &mut Vec<String>,
+) -> Result<()> {
+    use crate::elf::{context_from_elf, SectionIter, SymtabIter};
+
+    if elf.section_headers.len() != modified_elf.section_headers.len() {
+        mismatches.push("number of sections changed".to_string());
+        return Ok(());
+    }
+
+    // Section names may change
+    for (name, _) in SectionIter::from_elf(original, elf)?.collect::<Result<Vec<_>>>()? {
+        if let Some(name) = name {
+            allowed.push(range_of(&name, base));
+        }
+    }
+
+    // Symbol version names, their hashes, and the versions assigned to symbols may change
+    let versions = crate::elf::version::Versions::from_elf(original, elf)?;
+    for definition in &versions.definitions {
+        allowed.push(range_of(&definition.hash, base));
+        allowed.push(range_of(&definition.name, base));
+        allowed.push(range_of(&definition.name_offset, base));
+        for parent in &definition.parents {
+            allowed.push(range_of(parent, base));
+        }
+    }
+    for version in versions
+        .requirements
+        .iter()
+        .flat_map(|requirement| &requirement.versions)
+    {
+        allowed.push(range_of(&version.hash, base));
+        allowed.push(range_of(&version.name, base));
+    }
+    for version in &versions.symbols {
+        allowed.push(range_of(version, base));
+    }
+
+    // The shared object and dependency names may change, in place or by pointing to another
+    // string, along with the file names of the dependencies versions are required from
+    if let Some(dynamic) = crate::elf::dynamic::Dynamic::from_elf(original, elf)? {
+        for string in &dynamic.strings {
+            // The value follows the tag, and is the same size
+            let entry = range_of(&string.entry, base);
+            allowed.push(entry.start + entry.len() / 2..entry.end);
+            allowed.push(range_of(&string.name, base));
+        }
+    }
+    for requirement in &versions.requirements {
+        allowed.push(range_of(&requirement.file, base));
+        allowed.push(range_of(&requirement.file_offset, base));
+    }
+
+    // Symbol names and visibility may change
+    let ctx = context_from_elf(elf);
+    let st_other_offset = match ctx.container {
+        Container::Little => 13,
+        Container::Big => 5,
+    };
+    for (index, header) in elf.section_headers.iter().enumerate() {
+        if header.sh_type != SHT_SYMTAB && header.sh_type != SHT_DYNSYM {
+            continue;
+        }
+        let symbols = SymtabIter::from_section_header(original, header, &elf.section_headers, ctx)?
+            .collect::<Result<Vec<_>>>()?;
+        let modified_symbols = match SymtabIter::from_section_header(
+            modified,
+            &modified_elf.section_headers[index],
+            &modified_elf.section_headers,
+            ctx,
+        )
+        .and_then(|iter| iter.collect::<Result<Vec<_>>>())
+        {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                mismatches.push(format!("modified symbol table could not be read: {}", e));
+                continue;
+            }
+        };
+        if symbols.len() != modified_symbols.len() {
+            mismatches.push(format!(
+                "number of symbols in section {} changed from {} to {}",
+                index,
+                symbols.len(),
+                modified_symbols.len()
+            ));
+            continue;
+        }
+        for (symbol_index, ((name, sym), (_, modified_sym))) in
+            symbols.iter().zip(modified_symbols.iter()).enumerate()
+        {
+            let st_other = range_of(sym, base).start + st_other_offset;
+            allowed.push(st_other..st_other + 1);
+            if let Some(name) = name {
+                allowed.push(range_of(name, base));
+            }
+
+            let describe = || match name {
+                Some(name) => format!("symbol '{}'", name.value),
+                None => format!("symbol {} in section {}", symbol_index, index),
+            };
+            let fields = [
+                ("st_name", sym.st_name as u64, modified_sym.st_name as u64),
+                ("st_info", sym.st_info as u64, modified_sym.st_info as u64),
+                (
+                    "st_other (other than visibility)",
+                    (sym.st_other & !0x3) as u64,
+                    (modified_sym.st_other & !0x3) as u64,
+                ),
+                (
+                    "st_shndx",
+                    sym.st_shndx as u64,
+                    modified_sym.st_shndx as u64,
+                ),
+                ("st_value", sym.st_value, modified_sym.st_value),
+                ("st_size", sym.st_size, modified_sym.st_size),
+            ];
+            for (field, before, after) in fields.iter() {
+                if before != after {
+                    mismatches.push(format!(
+                        "{}: {} changed from {:#x} to {:#x}",
+                        describe(),
+                        field,
+                        before,
+                        after
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The fields of a Mach-O symbol that must be preserved, used to match symbols when the symbol
+/// table has been reordered.
+type NlistKey = (usize, u8, usize, u16, u64);
+
+fn nlist_key(nlist: &Nlist) -> NlistKey {
+    (
+        nlist.n_strx,
+        nlist.n_type & !N_PEXT,
+        nlist.n_sect,
+        nlist.n_desc,
+        nlist.n_value,
+    )
+}
+
+fn verify_mach(
+    original: &[u8],
+    modified: &[u8],
+    mach: &MachO,
+    modified_mach: &MachO,
+    base: usize,
+    allowed: &mut Vec<Range<usize>>,
+    mismatches: &mut Vec<String>,
+) -> Result<()> {
+    use crate::mach::{context_from_macho, SectionIter, SegmentIter, SymtabIter};
+
+    let ctx = context_from_macho(mach);
+
+    // Section and segment names may change
+    for (segname, sectname, _) in SectionIter::from_mach(original, mach)?
+        .collect::<Result<Vec<_>>>()?
+        .iter()
+    {
+        allowed.push(range_of(segname, base));
+        allowed.push(range_of(sectname, base));
+    }
+    for segname in SegmentIter::from_mach(original, mach).collect::<Result<Vec<_>>>()? {
+        allowed.push(range_of(&segname, base));
+    }
+
+    // Symbol names and visibility may change, and symbols may be reordered if LC_DYSYMTAB is
+    // present
+    let symbols = match SymtabIter::from_mach(original, mach) {
+        Some(iter) => iter.collect::<Result<Vec<_>>>()?,
+        None => return Ok(()),
+    };
+    let modified_symbols = match SymtabIter::from_mach(modified, modified_mach)
+        .map(|iter| iter.collect::<Result<Vec<_>>>())
+        .transpose()
+    {
+        Ok(Some(symbols)) => symbols,
+        Ok(None) => {
+            mismatches.push("symbol table was removed".to_string());
+            return Ok(());
+        }
+        Err(e) => {
+            mismatches.push(format!("modified symbol table could not be read: {}", e));
+            return Ok(());
+        }
+    };
+    let dysymtab = find_dysymtab(mach);
+    for (name, nlist) in &symbols {
+        if dysymtab.is_some() {
+            allowed.push(range_of(nlist, base));
+        } else {
+            // The n_type field follows the 4-byte n_strx field
+            let n_type = range_of(nlist, base).start + 4;
+            allowed.push(n_type..n_type + 1);
+        }
+        if let Some(name) = name {
+            allowed.push(range_of(name, base));
+        }
+    }
+    if symbols.len() != modified_symbols.len() {
+        mismatches.push(format!(
+            "number of symbols changed from {} to {}",
+            symbols.len(),
+            modified_symbols.len()
+        ));
+        return Ok(());
+    }
+
+    let keys = symbols
+        .iter()
+        .map(|(_, nlist)| nlist_key(nlist))
+        .collect::<Vec<_>>();
+    let modified_keys = modified_symbols
+        .iter()
+        .map(|(_, nlist)| nlist_key(nlist))
+        .collect::<Vec<_>>();
+    let mut sorted_keys = keys.clone();
+    let mut sorted_modified_keys = modified_keys.clone();
+    sorted_keys.sort_unstable();
+    sorted_modified_keys.sort_unstable();
+    if sorted_keys != sorted_modified_keys {
+        for (index, (name, _)) in symbols.iter().enumerate() {
+            if sorted_modified_keys.binary_search(&keys[index]).is_err() {
+                let name = name.as_ref().map(|name| name.value).unwrap_or("");
+                mismatches.push(format!("symbol '{}' was modified or removed", name));
+            }
+        }
+    }
+
+    let (command_offset, dysymtab) = match dysymtab {
+        Some(dysymtab) => dysymtab,
+        None => return Ok(()),
+    };
+    let modified_dysymtab = match find_dysymtab(modified_mach) {
+        Some((_, dysymtab)) => dysymtab,
+        None => {
+            mismatches.push("LC_DYSYMTAB was removed".to_string());
+            return Ok(());
+        }
+    };
+
+    // The partition fields follow the `cmd` and `cmdsize` fields
+    allowed.push(base + command_offset + 8..base + command_offset + 32);
+    if is_partitioned(original, mach)? && !is_partitioned(modified, modified_mach)? {
+        mismatches.push("symbols are no longer partitioned by LC_DYSYMTAB".to_string());
+    }
+
+    // Indirect symbols may be renumbered, but must refer to the same symbols
+    let indirectsymoff = dysymtab.indirectsymoff as usize;
+    allowed
+        .push(base + indirectsymoff..base + indirectsymoff + dysymtab.nindirectsyms as usize * 4);
+    if dysymtab.indirectsymoff != modified_dysymtab.indirectsymoff
+        || dysymtab.nindirectsyms != modified_dysymtab.nindirectsyms
+    {
+        mismatches.push("indirect symbol table was moved".to_string());
+    } else {
+        for index in 0..dysymtab.nindirectsyms as usize {
+            let offset = indirectsymoff + index * 4;
+            let before: u32 = original.pread_with(offset, ctx.le)?;
+            let after: u32 = modified.pread_with(offset, ctx.le)?;
+            let special = INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS;
+            let matches = if before & special != 0 || after & special != 0 {
+                before == after
+            } else {
+                keys.get(before as usize).is_some()
+                    && keys.get(before as usize) == modified_keys.get(after as usize)
+            };
+            if !matches {
+                mismatches.push(format!(
+                    "indirect symbol {} refers to a different symbol",
+                    index
+                ));
+            }
+        }
+    }
+
+    // Relocations may be renumbered, but must refer to the same symbols
+    for offset in relocation_offsets(original, mach, Some(&dysymtab))? {
+        allowed.push(base + offset + 4..base + offset + 8);
+        let before: u32 = original.pread_with(offset + 4, ctx.le)?;
+        let after: u32 = modified.pread_with(offset + 4, ctx.le)?;
+        let matches = match (
+            relocation_symbol(before, ctx.le),
+            relocation_symbol(after, ctx.le),
+        ) {
+            (Some(before_symbol), Some(after_symbol)) => {
+                let mask = match ctx.le {
+                    scroll::Endian::Little => 0xff00_0000,
+                    scroll::Endian::Big => 0x0000_00ff,
+                };
+                before & mask == after & mask
+                    && keys.get(before_symbol as usize).is_some()
+                    && keys.get(before_symbol as usize) == modified_keys.get(after_symbol as usize)
+            }
+            _ => before == after,
+        };
+        if !matches {
+            mismatches.push(format!(
+                "relocation at {:#x} refers to a different symbol",
+                base + offset
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::fixture;
+use symtool_backend::archive::{Archive, ArchiveMember};
+use symtool_backend::editor::Editor;
+use symtool_backend::error::Error;
+use symtool_backend::object::ArchiveKind;
+
+/// An archive member header, in the common format.
+fn member_header(name: &str, size: usize) -> Vec<u8> {
+    format!(
+        "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+        name, 0, 0, 0, 644, size
+    )
+    .into_bytes()
+}
+
+#[test]
+fn truncated_member_is_malformed() {
+    let object = fixture("strip.o");
+    let mut archive = b"!<arch>\n".to_vec();
+    archive.extend(member_header("strip.o/", object.len()));
+    archive.extend(&object[..object.len() / 2]);
+
+    match Editor::from_bytes(archive)
+        .hide("^global_used$")
+        .into_bytes()
+    {
+        Err(Error::Malformed(message)) => {
+            assert_eq!(message, "archive member extends past the end of the file")
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn gnu_archive_round_trips() {
+    let bytes = fixture("gnu.a");
+    let archive = Archive::parse(&bytes).unwrap();
+    assert_eq!(archive.kind, ArchiveKind::Gnu);
+    assert!(archive.symbol_table);
+    let names: Vec<_> = archive.members.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["a_long_member_name.o", "strx.o"]);
+    assert_eq!(archive.members[1].data, fixture("strx.o"));
+    assert_eq!(archive.write().unwrap(), bytes);
+}
+
+#[test]
+fn bsd_archive_round_trips() {
+    let bytes = fixture("bsd.a");
+    let archive = Archive::parse(&bytes).unwrap();
+    assert_eq!(archive.kind, ArchiveKind::Bsd);
+    assert!(archive.symbol_table);
+    let names: Vec<_> = archive.members.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["a_long_member_name.o", "strx.o"]);
+    assert_eq!(archive.write().unwrap(), bytes);
+}
+
+#[test]
+fn resized_members_are_rewritten() {
+    let mut archive = Archive::parse(&fixture("gnu.a")).unwrap();
+    archive.members[0].data.extend_from_slice(b"\0\0\0");
+    let written = Archive::parse(&archive.write().unwrap()).unwrap();
+    assert_eq!(written, archive);
+}
+
+#[test]
+fn growing_a_member_rewrites_the_archive() {
+    let library = fixture("libversioned.so.1");
+    let member = |name: &str| ArchiveMember {
+        name: name.to_string(),
+        date: "0".to_string(),
+        uid: "0".to_string(),
+        gid: "0".to_string(),
+        mode: "644".to_string(),
+        data: library.clone(),
+    };
+    let archive = Archive {
+        kind: ArchiveKind::Gnu,
+        members: vec![member("libversioned.so.1"), member("libkept.so.1")],
+        symbol_table: false,
+    };
+
+    let soname = "libversioned-with-a-longer-name.so.1";
+    let edited = Editor::from_bytes(archive.write().unwrap())
+        .set_soname(soname)
+        .member("^libversioned")
+        .into_bytes()
+        .unwrap();
+    let edited = Archive::parse(&edited).unwrap();
+    let sonames: Vec<_> = edited
+        .members
+        .iter()
+        .map(|member| {
+            goblin::elf::Elf::parse(&member.data)
+                .unwrap()
+                .soname
+                .map(str::to_string)
+        })
+        .collect();
+    assert_eq!(
+        sonames,
+        [
+            Some(soname.to_string()),
+            Some("libversioned.so.1".to_string())
+        ]
+    );
+    assert!(edited.members[0].data.len() > library.len());
+    assert_eq!(edited.members[1].data, library);
+}
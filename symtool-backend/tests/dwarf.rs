@@ -0,0 +1,46 @@
+mod common;
+
+use common::fixture;
+use symtool_backend::dwarf::names_from_elf;
+use symtool_backend::editor::Editor;
+use symtool_backend::error::Error;
+
+#[test]
+fn relocated_string_references_are_resolved() {
+    let bytes = fixture("strx.o");
+    let elf = goblin::elf::Elf::parse(&bytes).unwrap();
+    let names = names_from_elf(&bytes, &elf).unwrap();
+    let size = |value: &str| {
+        names
+            .iter()
+            .find(|name| name.value == value)
+            .map(|name| name.size())
+    };
+
+    // Both are only referenced, through `.debug_str_offsets` and directly, within
+    // `foo_value_impl`
+    assert_eq!(size("foo_value_impl"), Some(14));
+    assert_eq!(size("value_impl"), Some(10));
+    assert_eq!(size("impl"), Some(4));
+}
+
+#[test]
+fn renaming_a_string_referenced_by_index_fails() {
+    let result = Editor::from_bytes(fixture("strx.o"))
+        .rename("foo_value_impl", "foo_other_impx")
+        .rename_dwarf(true)
+        .into_bytes();
+    match result {
+        Err(Error::SharedString(mut affected)) => {
+            affected.sort();
+            assert_eq!(
+                affected,
+                vec![
+                    ("impl".to_string(), "impx".to_string()),
+                    ("value_impl".to_string(), "other_impx".to_string()),
+                ]
+            );
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
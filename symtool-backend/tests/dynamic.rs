@@ -0,0 +1,87 @@
+mod common;
+
+use common::fixture;
+use goblin::elf::dynamic::{DT_NEEDED, DT_SONAME, DT_STRSZ, DT_STRTAB};
+use goblin::elf::program_header::{PF_R, PT_LOAD};
+use goblin::elf::Elf;
+use symtool_backend::editor::Editor;
+
+/// The value of the dynamic entry `tag`.
+fn dynamic_value(elf: &Elf, tag: u64) -> u64 {
+    elf.dynamic
+        .as_ref()
+        .unwrap()
+        .dyns
+        .iter()
+        .find(|entry| entry.d_tag == tag)
+        .unwrap()
+        .d_val
+}
+
+/// The string at `offset` in a string table.
+fn string_at(strtab: &[u8], offset: u64) -> &str {
+    let string = &strtab[offset as usize..];
+    let end = string.iter().position(|b| *b == 0).unwrap();
+    std::str::from_utf8(&string[..end]).unwrap()
+}
+
+#[test]
+fn grown_string_table_is_loadable() {
+    let original = fixture("libversioned.so.1");
+    let soname = "libversioned-with-a-much-longer-name.so.1";
+    let needed = "libc-with-a-much-longer-name.so.6";
+    let grown = Editor::from_bytes(original.clone())
+        .set_soname(soname)
+        .replace_needed("libc.so.6", needed)
+        .into_bytes()
+        .unwrap();
+    assert!(grown.len() > original.len());
+    let elf = Elf::parse(&grown).unwrap();
+
+    // Loadable segments are sorted by address, don't overlap, and can be mapped
+    let loads: Vec<_> = elf
+        .program_headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .collect();
+    for pair in loads.windows(2) {
+        assert!(pair[0].p_vaddr + pair[0].p_memsz <= pair[1].p_vaddr);
+    }
+    for load in &loads {
+        assert_eq!(load.p_offset % load.p_align, load.p_vaddr % load.p_align);
+        assert!(load.p_offset + load.p_filesz <= grown.len() as u64);
+    }
+
+    // The program header table is mapped by a loadable segment
+    let size = u64::from(elf.header.e_phnum) * u64::from(elf.header.e_phentsize);
+    let phdrs = elf.header.e_phoff..elf.header.e_phoff + size;
+    assert!(loads
+        .iter()
+        .any(|load| load.p_offset <= phdrs.start && phdrs.end <= load.p_offset + load.p_filesz));
+
+    // DT_STRTAB and DT_STRSZ describe a readable string table containing the new names
+    let strtab = dynamic_value(&elf, DT_STRTAB);
+    let strsz = dynamic_value(&elf, DT_STRSZ);
+    let load = loads
+        .iter()
+        .find(|load| load.p_vaddr <= strtab && strtab + strsz <= load.p_vaddr + load.p_filesz)
+        .unwrap();
+    assert_ne!(load.p_flags & PF_R, 0);
+    let offset = (strtab - load.p_vaddr + load.p_offset) as usize;
+    let strtab = &grown[offset..offset + strsz as usize];
+    assert_eq!(strtab.last(), Some(&0));
+    assert_eq!(string_at(strtab, dynamic_value(&elf, DT_SONAME)), soname);
+    assert_eq!(string_at(strtab, dynamic_value(&elf, DT_NEEDED)), needed);
+
+    // Every other name is still found in the new string table
+    let original_elf = Elf::parse(&original).unwrap();
+    let names = |elf: &Elf| {
+        elf.dynsyms
+            .iter()
+            .map(|sym| elf.dynstrtab.get_at(sym.st_name).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&elf), names(&original_elf));
+    let verneed = elf.verneed.as_ref().unwrap().iter().next().unwrap();
+    assert_eq!(string_at(strtab, verneed.vn_file as u64), needed);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::fixture;
+use goblin::container::{Container, Ctx};
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::MachO;
+use scroll::{Endian, Pread};
+use symtool_backend::editor::Editor;
+
+/// The names of the symbols in the symbol table, in order.
+fn symbol_names(mach: &MachO) -> Vec<String> {
+    mach.symbols()
+        .map(|symbol| symbol.unwrap().0.to_string())
+        .collect()
+}
+
+/// The address of each relocation in `__text`, and the name of the symbol it refers to.
+fn relocations(bytes: &[u8], mach: &MachO) -> Vec<(i32, String)> {
+    let names = symbol_names(mach);
+    let ctx = Ctx::new(Container::Big, Endian::Little);
+    let mut relocations = Vec::new();
+    for segment in &mach.segments {
+        for (section, _) in segment.sections().unwrap() {
+            if section.name().unwrap() != "__text" {
+                continue;
+            }
+            for relocation in section.iter_relocations(bytes, ctx) {
+                let relocation = relocation.unwrap();
+                assert!(relocation.is_extern());
+                relocations.push((
+                    relocation.r_address,
+                    names[relocation.r_symbolnum()].clone(),
+                ));
+            }
+        }
+    }
+    relocations.sort();
+    relocations
+}
+
+/// The name of the symbol referred to by each entry of the indirect symbol table.
+fn indirect_symbols(bytes: &[u8], mach: &MachO) -> Vec<String> {
+    let names = symbol_names(mach);
+    let dysymtab = mach
+        .load_commands
+        .iter()
+        .find_map(|command| match command.command {
+            CommandVariant::Dysymtab(dysymtab) => Some(dysymtab),
+            _ => None,
+        })
+        .unwrap();
+    (0..dysymtab.nindirectsyms as usize)
+        .map(|index| {
+            let offset = dysymtab.indirectsymoff as usize + index * 4;
+            let symbol: u32 = bytes.pread_with(offset, Endian::Little).unwrap();
+            names[symbol as usize].clone()
+        })
+        .collect()
+}
+
+#[test]
+fn renaming_repartitions_symbols() {
+    let original = fixture("dysymtab.o");
+    let mach = MachO::parse(&original, 0).unwrap();
+    assert_eq!(
+        symbol_names(&mach),
+        ["_local", "_alpha", "_beta", "_undef_alpha", "_undef_beta"]
+    );
+    assert_eq!(
+        relocations(&original, &mach),
+        [
+            (0x1, "_undef_alpha".to_string()),
+            (0x6, "_undef_beta".to_string()),
+            (0xc, "_local".to_string()),
+        ]
+    );
+    assert_eq!(
+        indirect_symbols(&original, &mach),
+        ["_undef_alpha", "_undef_beta"]
+    );
+
+    // Both renamed symbols move to the end of their partitions
+    let modified = Editor::from_bytes(original)
+        .rename("_alpha", "_omega")
+        .rename("_undef_alpha", "_undef_omega")
+        .verify(true)
+        .into_bytes()
+        .unwrap();
+    let mach = MachO::parse(&modified, 0).unwrap();
+    assert!(symtool_backend::mach::dysymtab::is_partitioned(&modified, &mach).unwrap());
+    assert_eq!(
+        symbol_names(&mach),
+        ["_local", "_beta", "_omega", "_undef_beta", "_undef_omega"]
+    );
+    assert_eq!(
+        relocations(&modified, &mach),
+        [
+            (0x1, "_undef_omega".to_string()),
+            (0x6, "_undef_beta".to_string()),
+            (0xc, "_local".to_string()),
+        ]
+    );
+    assert_eq!(
+        indirect_symbols(&modified, &mach),
+        ["_undef_omega", "_undef_beta"]
+    );
+}
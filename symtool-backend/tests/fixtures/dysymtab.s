@@ -0,0 +1,22 @@
+	.section	__TEXT,__text,regular,pure_instructions
+	.globl	_beta
+_beta:
+	callq	_undef_alpha
+	callq	_undef_beta
+	retq
+
+	.globl	_alpha
+_alpha:
+	callq	_local
+	retq
+
+_local:
+	retq
+
+	.section	__DATA,__nl_symbol_ptr,non_lazy_symbol_pointers
+	.indirect_symbol	_undef_alpha
+	.quad	0
+	.indirect_symbol	_undef_beta
+	.quad	0
+
+.subsections_via_symbols
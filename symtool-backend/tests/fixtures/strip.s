@@ -0,0 +1,32 @@
+# Symbols to strip, followed by symbols referenced by relocations and a section group, so that
+# stripping renumbers every reference.  `.shndx` is space for an extended section index table,
+# which is only emitted for objects with many sections.
+
+	.text
+local_unused:
+	ret
+local_used:
+	ret
+
+	.globl	global_unused
+global_unused:
+	ret
+
+	.globl	global_used
+global_used:
+	call	local_used
+	call	inline_fn
+	ret
+
+	.section	.text.inline_fn,"axG",@progbits,inline_fn,comdat
+	.weak	inline_fn
+inline_fn:
+	call	global_used
+	ret
+
+	.data
+	.quad	global_used
+	.quad	inline_fn
+
+	.section	.shndx,"",@progbits
+	.zero	64
@@ -0,0 +1,73 @@
+# A DWARF 5 compile unit referencing names through .debug_str_offsets (DW_FORM_strx1) and
+# directly (DW_FORM_strp).  Both refer into the middle of `foo_value_impl`, and both are
+# relocated, so they are only correct once the relocation addends are applied.
+
+	.text
+	.globl	foo_value_impl
+	.type	foo_value_impl, @function
+foo_value_impl:
+	ret
+	.size	foo_value_impl, .-foo_value_impl
+
+	.section	.debug_abbrev,"",@progbits
+	.uleb128 1			# compile unit
+	.uleb128 0x11			# DW_TAG_compile_unit
+	.byte	1			# DW_CHILDREN_yes
+	.uleb128 0x25			# DW_AT_producer
+	.uleb128 0x25			# DW_FORM_strx1
+	.uleb128 0x03			# DW_AT_name
+	.uleb128 0x25			# DW_FORM_strx1
+	.uleb128 0x72			# DW_AT_str_offsets_base
+	.uleb128 0x17			# DW_FORM_sec_offset
+	.byte	0, 0
+	.uleb128 2			# subprogram named by index
+	.uleb128 0x2e			# DW_TAG_subprogram
+	.byte	0			# DW_CHILDREN_no
+	.uleb128 0x03			# DW_AT_name
+	.uleb128 0x25			# DW_FORM_strx1
+	.byte	0, 0
+	.uleb128 3			# subprogram named by offset
+	.uleb128 0x2e			# DW_TAG_subprogram
+	.byte	0			# DW_CHILDREN_no
+	.uleb128 0x6e			# DW_AT_linkage_name
+	.uleb128 0x0e			# DW_FORM_strp
+	.byte	0, 0
+	.byte	0
+
+	.section	.debug_info,"",@progbits
+	.long	.Linfo_end-.Linfo_start	# unit_length
+.Linfo_start:
+	.short	5			# version
+	.byte	1			# DW_UT_compile
+	.byte	8			# address_size
+	.long	.debug_abbrev		# debug_abbrev_offset
+	.uleb128 1
+	.byte	0			# DW_AT_producer
+	.byte	1			# DW_AT_name
+	.long	.Lstr_offsets_base	# DW_AT_str_offsets_base
+	.uleb128 2
+	.byte	3			# DW_AT_name
+	.uleb128 3
+	.long	.debug_str+30		# DW_AT_linkage_name, `impl`
+	.byte	0
+.Linfo_end:
+
+	.section	.debug_str_offsets,"",@progbits
+	.long	.Lstr_offsets_end-.Lstr_offsets_start	# unit_length
+.Lstr_offsets_start:
+	.short	5			# version
+	.short	0			# padding
+.Lstr_offsets_base:
+	.long	.Lproducer
+	.long	.Lname
+	.long	.Lfoo_value_impl
+	.long	.debug_str+24		# `value_impl`
+.Lstr_offsets_end:
+
+	.section	.debug_str,"MS",@progbits,1
+.Lproducer:
+	.asciz	"hand written"
+.Lname:
+	.asciz	"strx.c"
+.Lfoo_value_impl:			# at offset 20
+	.asciz	"foo_value_impl"
@@ -0,0 +1,9 @@
+#include <stdio.h>
+
+int versioned_value(int x) {
+    return x + 1;
+}
+
+void versioned_print(int x) {
+    printf("%d\n", versioned_value(x));
+}
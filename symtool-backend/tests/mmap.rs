@@ -0,0 +1,64 @@
+mod common;
+
+use common::fixture;
+use std::path::PathBuf;
+use symtool_backend::editor::Editor;
+
+/// A path in the temporary directory, unique to this process.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("symtool-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn mapped_output_matches_buffered_output() {
+    let original = fixture("dysymtab.o");
+    let input = temp_path("mmap-input.o");
+    let output = temp_path("mmap-output.o");
+    std::fs::write(&input, &original).unwrap();
+
+    // Renaming `_alpha` to `_omega` reorders the symbol table, and `_alpha` is stored within
+    // `_undef_alpha`
+    let edit = |editor: Editor| {
+        editor
+            .hide("^_beta$")
+            .rename("_alpha", "_omega")
+            .rename("_undef_alpha", "_undef_omega")
+            .verify(true)
+    };
+    let buffered = edit(Editor::from_bytes(original.clone()))
+        .into_bytes()
+        .unwrap();
+    edit(Editor::open(&input).unwrap().mmap(true))
+        .write(&output)
+        .unwrap();
+    let mapped = std::fs::read(&output).unwrap();
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert_ne!(buffered, original);
+    assert!(
+        buffered == mapped,
+        "mapped output differs from buffered output"
+    );
+}
+
+#[test]
+fn failed_edit_keeps_existing_output() {
+    let input = temp_path("keep-input.o");
+    let output = temp_path("keep-output.o");
+    std::fs::write(&input, fixture("dysymtab.o")).unwrap();
+    std::fs::write(&output, b"existing").unwrap();
+
+    let result = Editor::open(&input)
+        .unwrap()
+        .mmap(true)
+        .hide("^_missing$")
+        .require_match(true)
+        .write(&output);
+    let existing = std::fs::read(&output);
+    std::fs::remove_file(&input).unwrap();
+    let _ = std::fs::remove_file(&output);
+
+    assert!(result.is_err());
+    assert_eq!(existing.unwrap(), b"existing");
+}
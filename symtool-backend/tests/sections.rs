@@ -0,0 +1,47 @@
+mod common;
+
+use common::fixture;
+use goblin::mach::MachO;
+use symtool_backend::editor::Editor;
+use symtool_backend::error::Error;
+
+/// The segment and section names of every section.
+fn section_names(bytes: &[u8]) -> Vec<(String, String)> {
+    let mach = MachO::parse(bytes, 0).unwrap();
+    let mut names = Vec::new();
+    for segment in &mach.segments {
+        for (section, _) in segment.sections().unwrap() {
+            names.push((
+                section.segname().unwrap().to_string(),
+                section.name().unwrap().to_string(),
+            ));
+        }
+    }
+    names
+}
+
+#[test]
+fn renaming_segment_renames_its_sections() {
+    let modified = Editor::from_bytes(fixture("dysymtab.o"))
+        .rename_segment("__DATA", "__DATB")
+        .rename_section("__DATA,__nl_symbol_ptr", "__DATB,__nl_symbol_pts")
+        .verify(true)
+        .into_bytes()
+        .unwrap();
+    assert_eq!(
+        section_names(&modified),
+        [
+            ("__TEXT".to_string(), "__text".to_string()),
+            ("__DATB".to_string(), "__nl_symbol_pts".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn section_rename_conflicting_with_segment_rename_fails() {
+    let result = Editor::from_bytes(fixture("dysymtab.o"))
+        .rename_segment("__DATA", "__DATB")
+        .rename_section("__DATA,__nl_symbol_ptr", "__DATA,__nl_symbol_pts")
+        .into_bytes();
+    assert!(matches!(result, Err(Error::InvalidArgument(_))));
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::fixture;
+use goblin::elf::section_header::{SHT_GROUP, SHT_SYMTAB, SHT_SYMTAB_SHNDX};
+use goblin::elf::Elf;
+use scroll::{Pread, Pwrite, LE};
+use symtool_backend::editor::Editor;
+use symtool_backend::error::Error;
+
+/// The size of a section header in a 64-bit ELF file.
+const SHDR_SIZE: usize = 64;
+
+/// The name of the symbol at `index`.
+fn symbol_name(elf: &Elf, index: usize) -> String {
+    let sym = elf.syms.get(index).unwrap();
+    elf.strtab.get_at(sym.st_name).unwrap().to_string()
+}
+
+/// The name of the symbol each relocation refers to, in order.
+fn relocation_targets(elf: &Elf) -> Vec<String> {
+    elf.shdr_relocs
+        .iter()
+        .flat_map(|(_, relocations)| relocations.iter())
+        .map(|relocation| symbol_name(elf, relocation.r_sym))
+        .collect()
+}
+
+/// The extended section index table, read from `bytes`.
+fn shndx_entries(bytes: &[u8], elf: &Elf) -> Vec<u32> {
+    let header = elf
+        .section_headers
+        .iter()
+        .find(|header| header.sh_type == SHT_SYMTAB_SHNDX)
+        .unwrap();
+    (0..header.sh_size as usize / 4)
+        .map(|index| {
+            bytes
+                .pread_with(header.sh_offset as usize + index * 4, LE)
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn stripping_compacts_and_renumbers_references() {
+    // Turn the placeholder `.shndx` into an extended section index table with a distinct entry
+    // for each symbol
+    let mut original = fixture("strip.o");
+    let elf = Elf::parse(&original).unwrap();
+    let symtab = elf
+        .section_headers
+        .iter()
+        .position(|header| header.sh_type == SHT_SYMTAB)
+        .unwrap();
+    let (index, shndx) = elf
+        .section_headers
+        .iter()
+        .enumerate()
+        .find(|(_, header)| elf.shdr_strtab.get_at(header.sh_name) == Some(".shndx"))
+        .unwrap();
+    let (shndx_offset, count) = (shndx.sh_offset as usize, elf.syms.len());
+    assert!(count * 4 <= shndx.sh_size as usize);
+    let header = elf.header.e_shoff as usize + index * SHDR_SIZE;
+    for symbol in 0..count {
+        original
+            .pwrite_with(0x100 + symbol as u32, shndx_offset + symbol * 4, LE)
+            .unwrap();
+    }
+    original
+        .pwrite_with(SHT_SYMTAB_SHNDX, header + 4, LE)
+        .unwrap();
+    original
+        .pwrite_with((count * 4) as u64, header + 32, LE)
+        .unwrap();
+    original
+        .pwrite_with(symtab as u32, header + 40, LE)
+        .unwrap();
+    let elf = Elf::parse(&original).unwrap();
+
+    let stripped = Editor::from_bytes(original.clone())
+        .strip("^(local_unused|global_unused)$")
+        .verify(true)
+        .into_bytes()
+        .unwrap();
+    let stripped_elf = Elf::parse(&stripped).unwrap();
+
+    let names = |elf: &Elf| {
+        (0..elf.syms.len())
+            .map(|index| symbol_name(elf, index))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(&stripped_elf),
+        ["", "local_used", "global_used", "inline_fn"]
+    );
+    let symtab = &stripped_elf.section_headers[symtab];
+    assert_eq!(symtab.sh_info, 2);
+
+    // Relocations and the group signature refer to the same symbols, at their new indices
+    assert_eq!(relocation_targets(&elf), relocation_targets(&stripped_elf));
+    assert_eq!(
+        stripped_elf
+            .shdr_relocs
+            .iter()
+            .flat_map(|(_, relocations)| relocations.iter())
+            .map(|relocation| relocation.r_sym)
+            .collect::<Vec<_>>(),
+        [3, 2, 2, 3]
+    );
+    let group = |elf: &Elf| {
+        elf.section_headers
+            .iter()
+            .find(|header| header.sh_type == SHT_GROUP)
+            .unwrap()
+            .sh_info as usize
+    };
+    assert_eq!(group(&elf), 5);
+    assert_eq!(group(&stripped_elf), 3);
+    assert_eq!(symbol_name(&stripped_elf, 3), "inline_fn");
+
+    // The extended section indices are compacted along with the symbols, and the vacated entries
+    // are zeroed
+    assert_eq!(
+        shndx_entries(&stripped, &stripped_elf),
+        [0x100, 0x102, 0x104, 0x105]
+    );
+    assert!(stripped[shndx_offset + 16..shndx_offset + count * 4]
+        .iter()
+        .all(|b| *b == 0));
+}
+
+#[test]
+fn stripping_a_referenced_symbol_fails() {
+    let result = Editor::from_bytes(fixture("strip.o"))
+        .strip("^global_used$")
+        .into_bytes();
+    match result {
+        Err(Error::ReferencedSymbol(name)) => {
+            assert_eq!(name, "global_used")
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
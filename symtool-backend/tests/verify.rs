@@ -0,0 +1,45 @@
+mod common;
+
+use common::fixture;
+use std::ops::Range;
+use symtool_backend::editor::Editor;
+use symtool_backend::error::Error;
+use symtool_backend::verify::{verify_with, VerifyOptions};
+
+/// The ranges of bytes that differ between two equally sized binaries.
+fn differences(original: &[u8], modified: &[u8]) -> Vec<Range<usize>> {
+    (0..original.len())
+        .filter(|&index| original[index] != modified[index])
+        .map(|index| index..index + 1)
+        .collect()
+}
+
+#[test]
+fn changes_outside_intended_patches_fail() {
+    let original = fixture("dysymtab.o");
+    let hidden = Editor::from_bytes(original.clone())
+        .hide("^_beta$")
+        .into_bytes()
+        .unwrap();
+    let intended = VerifyOptions {
+        intended: Some(differences(&original, &hidden)),
+        ..VerifyOptions::default()
+    };
+    verify_with(&original, &hidden, &intended).unwrap();
+
+    // A name may change, but this one wasn't meant to
+    let mut corrupted = hidden;
+    let local = corrupted
+        .windows(7)
+        .position(|window| window == b"_local\0")
+        .unwrap();
+    corrupted[local + 3] = b'k';
+    verify_with(&original, &corrupted, &VerifyOptions::default()).unwrap();
+    match verify_with(&original, &corrupted, &intended) {
+        Err(Error::Verification(mismatches)) => {
+            assert_eq!(mismatches.len(), 1, "{:?}", mismatches);
+            assert!(mismatches[0].starts_with("unexpected change"));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
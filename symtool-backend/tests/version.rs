@@ -0,0 +1,90 @@
+mod common;
+
+use common::fixture;
+use goblin::elf::Elf;
+use symtool_backend::editor::Editor;
+
+/// The System V ELF hash of a version name.
+fn elf_hash(name: &str) -> u32 {
+    let mut hash: u32 = 0;
+    for byte in name.bytes() {
+        hash = (hash << 4).wrapping_add(byte.into());
+        let high = hash & 0xf000_0000;
+        if high != 0 {
+            hash ^= high >> 24;
+        }
+        hash &= !high;
+    }
+    hash
+}
+
+/// The name and hash of each version.
+type Hashes = Vec<(String, u32)>;
+
+/// The name and hash of each version definition and requirement.
+fn versions(bytes: &[u8]) -> (Hashes, Hashes) {
+    let elf = Elf::parse(bytes).unwrap();
+    let name = |offset| elf.dynstrtab.get_at(offset).unwrap().to_string();
+    let definitions = elf
+        .verdef
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|verdef| (name(verdef.iter().next().unwrap().vda_name), verdef.vd_hash))
+        .collect();
+    let requirements = elf
+        .verneed
+        .as_ref()
+        .unwrap()
+        .iter()
+        .flat_map(|verneed| {
+            verneed
+                .iter()
+                .map(|vernaux| (name(vernaux.vna_name), vernaux.vna_hash))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    (definitions, requirements)
+}
+
+#[test]
+fn renaming_versions_updates_their_hashes() {
+    let original = fixture("libversioned.so.1");
+    let (definitions, requirements) = versions(&original);
+    assert_eq!(
+        definitions,
+        [
+            (
+                "libversioned.so.1".to_string(),
+                elf_hash("libversioned.so.1")
+            ),
+            ("VERS_1".to_string(), elf_hash("VERS_1")),
+        ]
+    );
+    assert_eq!(
+        requirements,
+        [("GLIBC_2.2.5".to_string(), elf_hash("GLIBC_2.2.5"))]
+    );
+
+    let renamed = Editor::from_bytes(original)
+        .rename_version("VERS_1", "VERS_2")
+        .rename_version("GLIBC_2.2.5", "GLIBC_2.2")
+        .verify(true)
+        .into_bytes()
+        .unwrap();
+    let (definitions, requirements) = versions(&renamed);
+    assert_eq!(
+        definitions,
+        [
+            (
+                "libversioned.so.1".to_string(),
+                elf_hash("libversioned.so.1")
+            ),
+            ("VERS_2".to_string(), elf_hash("VERS_2")),
+        ]
+    );
+    assert_eq!(
+        requirements,
+        [("GLIBC_2.2".to_string(), elf_hash("GLIBC_2.2"))]
+    );
+}
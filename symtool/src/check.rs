@@ -0,0 +1,98 @@
+//! The `check` subcommand, which checks the symbols exported by a binary against a policy.
+
+use crate::report::context_json;
+use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use symtool_backend::check::{check, Policy, Reason, Violation};
+use symtool_backend::object::TransformOptions;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("check")
+        .about("Checks the symbols exported by a binary or archive against a policy")
+        .long_about("Checks that the symbols exported by a binary or archive, which are the defined global and weak symbols that are not hidden, are allowed by a policy. The input is not modified. Exits with status 1 if any exported symbol violates the policy.")
+        .arg(
+            Arg::with_name("allow")
+                .long("allow")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN")
+                .help("Allows exporting symbols with names matching regex PATTERN")
+                .long_help("Allows exporting symbols with names matching regex PATTERN. If any patterns are allowed, exported symbols that don't match one of them violate the policy."),
+        )
+        .arg(
+            Arg::with_name("deny")
+                .long("deny")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN")
+                .help("Denies exporting symbols with names matching regex PATTERN")
+                .long_help("Denies exporting symbols with names matching regex PATTERN. --deny takes precedence over --allow when both patterns match a symbol name."),
+        )
+        .group(
+            ArgGroup::with_name("policy")
+                .args(&["allow", "deny"])
+                .multiple(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Prints the violations as JSON"),
+        )
+        .arg(
+            Arg::with_name("skip-unknown")
+                .long("skip-unknown")
+                .help("Ignores archive members that are not objects")
+                .long_help("Ignores archive members that are not ELF or Mach-O objects, such as LLVM bitcode or text files. By default, such members cause an error."),
+        )
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Path to object or archive file, or - for standard input")
+                .required(true)
+                .index(1),
+        )
+}
+
+fn violation_json(violation: &Violation) -> Value {
+    let mut value = context_json(&violation.context);
+    value["symbol"] = json!(violation.symbol.name);
+    value["binding"] = json!(violation.symbol.binding.to_string());
+    value["kind"] = json!(violation.symbol.kind.to_string());
+    value["visibility"] = json!(violation.symbol.visibility.to_string());
+    value["pattern"] = match &violation.reason {
+        Reason::Denied(pattern) => json!(pattern),
+        Reason::NotAllowed => Value::Null,
+    };
+    value
+}
+
+/// Returns true if any exported symbol violates the policy.
+pub fn run(matches: &ArgMatches) -> Result<bool, Box<dyn std::error::Error>> {
+    let options = TransformOptions {
+        skip_unknown: matches.is_present("skip-unknown"),
+        ..Default::default()
+    };
+    let mut policy = Policy::new();
+    for pattern in matches.values_of("allow").unwrap_or_default() {
+        policy = policy.allow(pattern);
+    }
+    for pattern in matches.values_of("deny").unwrap_or_default() {
+        policy = policy.deny(pattern);
+    }
+    let input = crate::read_input(matches.value_of("INPUT").unwrap())?;
+    let violations = check(&input, &options, &policy)?;
+    if matches.is_present("json") {
+        let json = json!({
+            "violations": violations.iter().map(violation_json).collect::<Vec<_>>(),
+            "count": violations.len(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        for violation in &violations {
+            println!("{}", violation);
+        }
+    }
+    Ok(!violations.is_empty())
+}
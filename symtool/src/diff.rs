@@ -0,0 +1,136 @@
+//! The `diff` subcommand, which compares the symbols exported by two binaries.
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use symtool_backend::diff::{diff, ObjectDiff, SymbolDiff, SymbolInfo};
+use symtool_backend::object::TransformOptions;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("diff")
+        .about("Compares the symbols exported by two binaries or archives")
+        .long_about("Compares the symbols exported by two binaries or archives, and reports the symbols added, removed, or changed in each object or archive member. Exits with status 1 if any symbols differ.")
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Prints the differences as JSON"),
+        )
+        .arg(
+            Arg::with_name("skip-unknown")
+                .long("skip-unknown")
+                .help("Ignores archive members that are not objects")
+                .long_help("Ignores archive members that are not ELF or Mach-O objects, such as LLVM bitcode or text files. By default, such members cause an error."),
+        )
+        .arg(
+            Arg::with_name("OLD")
+                .help("Path to the original object or archive file, or - for standard input")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("NEW")
+                .help("Path to the updated object or archive file, or - for standard input")
+                .required(true)
+                .index(2),
+        )
+}
+
+fn label(object: &ObjectDiff) -> Option<String> {
+    match (&object.architecture, &object.member) {
+        (Some(architecture), Some(member)) => {
+            Some(format!("{} slice, archive member {}", architecture, member))
+        }
+        (Some(architecture), None) => Some(format!("{} slice", architecture)),
+        (None, Some(member)) => Some(format!("archive member {}", member)),
+        (None, None) => None,
+    }
+}
+
+fn print_text(objects: &[ObjectDiff]) {
+    for object in objects {
+        if let Some(label) = label(object) {
+            println!("{}:", label);
+        }
+        for symbol in &object.symbols {
+            match symbol {
+                SymbolDiff::Added(new) => println!("+ {}", new),
+                SymbolDiff::Removed(old) => println!("- {}", old),
+                SymbolDiff::Changed { old, new } => println!(
+                    "~ {} ({}, {}, {} -> {}, {}, {})",
+                    old.name,
+                    old.binding,
+                    old.kind,
+                    old.visibility,
+                    new.binding,
+                    new.kind,
+                    new.visibility
+                ),
+            }
+        }
+    }
+}
+
+fn symbol_json(symbol: &SymbolInfo) -> Value {
+    json!({
+        "name": symbol.name,
+        "binding": symbol.binding.to_string(),
+        "kind": symbol.kind.to_string(),
+        "visibility": symbol.visibility.to_string(),
+    })
+}
+
+fn object_json(object: &ObjectDiff) -> Value {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for symbol in &object.symbols {
+        match symbol {
+            SymbolDiff::Added(new) => added.push(symbol_json(new)),
+            SymbolDiff::Removed(old) => removed.push(symbol_json(old)),
+            SymbolDiff::Changed { old, new } => changed.push(json!({
+                "name": old.name,
+                "old": symbol_json(old),
+                "new": symbol_json(new),
+            })),
+        }
+    }
+    json!({
+        "architecture": object.architecture,
+        "member": object.member,
+        "added": added,
+        "removed": removed,
+        "changed": changed,
+    })
+}
+
+fn to_json(objects: &[ObjectDiff]) -> Value {
+    let count = |f: fn(&SymbolDiff) -> bool| {
+        objects
+            .iter()
+            .flat_map(|object| &object.symbols)
+            .filter(|symbol| f(symbol))
+            .count()
+    };
+    json!({
+        "objects": objects.iter().map(object_json).collect::<Vec<_>>(),
+        "added": count(|symbol| matches!(symbol, SymbolDiff::Added(_))),
+        "removed": count(|symbol| matches!(symbol, SymbolDiff::Removed(_))),
+        "changed": count(|symbol| matches!(symbol, SymbolDiff::Changed { .. })),
+    })
+}
+
+/// Returns true if the binaries differ.
+pub fn run(matches: &ArgMatches) -> Result<bool, Box<dyn std::error::Error>> {
+    let options = TransformOptions {
+        skip_unknown: matches.is_present("skip-unknown"),
+        ..Default::default()
+    };
+    let old = crate::read_input(matches.value_of("OLD").unwrap())?;
+    let new = crate::read_input(matches.value_of("NEW").unwrap())?;
+    let objects = diff(&old, &new, &options)?;
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&to_json(&objects))?);
+    } else {
+        print_text(&objects);
+    }
+    Ok(!objects.is_empty())
+}
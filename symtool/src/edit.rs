@@ -0,0 +1,274 @@
+//! The `edit` subcommand, which edits symbols in a binary.
+//!
+//! Editing is also the default when no subcommand is given, so these arguments are also
+//! accepted by the top-level command.
+
+use crate::report;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::io::Write;
+use symtool_backend::editor::Editor;
+
+/// The arguments for editing a binary.
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("verbose")
+            .long("verbose")
+            .short("v")
+            .help("Print information for each operation performed"),
+        Arg::with_name("verify")
+            .long("verify")
+            .help("Verifies that only symbol names and visibility were modified")
+            .long_help(
+                "Verifies the output by parsing it again and comparing every symbol table to the input. Fails if anything other than the intended symbol, section, segment, symbol version, and shared object names, symbol visibility and versions, or archive member timestamps, owners, and modes changed, or if symbols other than those stripped were removed. Growing the dynamic string table is not verified."
+            ),
+        Arg::with_name("require-match")
+            .long("require-match")
+            .help("Fails if any pattern or rename does not match a name")
+            .long_help(
+                "Fails if any --hidden, --default, or --strip pattern, or any --rename, --rename-section, --rename-segment, --rename-version, or --replace-needed OLD-NAME, any --set-symbol-version SYMBOL, or --set-soname NAME does not match a name in any object. Without this option, a warning is printed for each unmatched pattern or rename."
+            ),
+        Arg::with_name("report")
+            .long("report")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Writes a JSON report of every change to FILE")
+            .long_help(
+                "Writes a JSON report to FILE, listing every change made to each object or archive member and the number of names matched by each pattern and rename."
+            ),
+        Arg::with_name("rename")
+            .long("rename")
+            .number_of_values(2)
+            .multiple(true)
+            .value_names(&["OLD-NAME", "NEW-NAME"])
+            .help("Renames symbols named OLD-NAME to NEW-NAME")
+            .long_help(
+                "Renames symbols named OLD-NAME to NEW-NAME. Since string tables are simply patched and not rewritten, NEW-NAME must not have more characters than OLD-NAME. Mach-O STABS debugging entries naming the symbol are also renamed."
+            ),
+        Arg::with_name("rename-dwarf")
+            .long("rename-dwarf")
+            .help("Also renames symbols in DWARF debug information")
+            .long_help(
+                "Also renames symbols in DWARF debug information, so that debuggers show the new names. Strings in the DWARF string tables are shared, so every use of a renamed string is renamed, not only names and linkage names of functions and variables. The same length constraints apply as when renaming symbols."
+            ),
+        Arg::with_name("rename-section")
+            .long("rename-section")
+            .number_of_values(2)
+            .multiple(true)
+            .value_names(&["OLD-NAME", "NEW-NAME"])
+            .help("Renames sections named OLD-NAME to NEW-NAME")
+            .long_help(
+                "Renames sections named OLD-NAME to NEW-NAME. Mach-O sections may be qualified with their segment name, as in SEGMENT,SECTION. ELF section names are patched in place, so NEW-NAME must not have more characters than OLD-NAME. Mach-O section names must not have more than 16 characters."
+            ),
+        Arg::with_name("rename-segment")
+            .long("rename-segment")
+            .number_of_values(2)
+            .multiple(true)
+            .value_names(&["OLD-NAME", "NEW-NAME"])
+            .help("Renames Mach-O segments named OLD-NAME to NEW-NAME")
+            .long_help(
+                "Renames Mach-O segments named OLD-NAME to NEW-NAME, including the segment names of their sections. Segment names must not have more than 16 characters. Sections in the segment renamed with --rename-section SEGMENT,SECTION must be renamed to the new segment."
+            ),
+        Arg::with_name("rename-version")
+            .long("rename-version")
+            .number_of_values(2)
+            .multiple(true)
+            .value_names(&["OLD-NAME", "NEW-NAME"])
+            .help("Renames ELF symbol versions named OLD-NAME to NEW-NAME")
+            .long_help(
+                "Renames ELF symbol versions named OLD-NAME to NEW-NAME, in both version definitions and the versions required from dependencies, along with versioned symbol names such as foo@OLD-NAME. NEW-NAME must not have more characters than OLD-NAME."
+            ),
+        Arg::with_name("set-symbol-version")
+            .long("set-symbol-version")
+            .number_of_values(2)
+            .multiple(true)
+            .value_names(&["SYMBOL", "VERSION"])
+            .help("Assigns the ELF symbol version VERSION to the dynamic symbol SYMBOL")
+            .long_help(
+                "Assigns the ELF symbol version VERSION to the dynamic symbol SYMBOL. VERSION must be defined by the binary or required from one of its dependencies, and is named as it was before any --rename-version."
+            ),
+        Arg::with_name("set-soname")
+            .long("set-soname")
+            .takes_value(true)
+            .value_name("NAME")
+            .help("Sets the name (DT_SONAME) of an ELF shared object to NAME")
+            .long_help(
+                "Sets the name (DT_SONAME) of an ELF shared object to NAME, like patchelf --set-soname. The shared object must already have a name. If NAME is longer than the original, the dynamic string table is grown, as with --replace-needed."
+            ),
+        Arg::with_name("replace-needed")
+            .long("replace-needed")
+            .number_of_values(2)
+            .multiple(true)
+            .value_names(&["OLD-NAME", "NEW-NAME"])
+            .help("Replaces the ELF dependency (DT_NEEDED) OLD-NAME with NEW-NAME")
+            .long_help(
+                "Replaces the ELF dependency (DT_NEEDED) OLD-NAME with NEW-NAME, like patchelf --replace-needed, including the file name of the versions required from it. Names are replaced in place if they fit. Otherwise, the dynamic string table is moved to a new segment at the end of the file, which is not checked by --verify. Archives containing shared objects that grow are rewritten, regenerating their symbol and long name tables."
+            ),
+        Arg::with_name("deterministic")
+            .long("deterministic")
+            .help("Zeroes archive member timestamps and owners, like ar D")
+            .long_help(
+                "Zeroes the timestamps, user IDs, and group IDs of archive members and sets their modes to 644, like ar D, so the output does not depend on when or by whom the archive was created."
+            ),
+        Arg::with_name("mmap")
+            .long("mmap")
+            .help("Edits a copy-on-write memory mapping of the input, reducing memory use")
+            .long_help(
+                "Edits a private, copy-on-write memory mapping of the input, rather than reading the entire input into memory. Only the parts of the file that are read are loaded, and only the parts that are modified are copied, which greatly reduces memory use for large archives. OUTPUT is written by copying INPUT and then writing the modified parts. INPUT and OUTPUT must be different files. Has no effect if INPUT or OUTPUT is -. Note that --verify reads the entire input, and binaries whose dynamic string table grows are edited in memory."
+            ),
+        Arg::with_name("member")
+            .long("member")
+            .takes_value(true)
+            .value_name("PATTERN")
+            .help("Only edits archive members with names matching regex PATTERN")
+            .long_help(
+                "Only edits archive members with names matching regex PATTERN. Other members are left unmodified."
+            ),
+        Arg::with_name("exclude-member")
+            .long("exclude-member")
+            .takes_value(true)
+            .value_name("PATTERN")
+            .help("Leaves archive members with names matching regex PATTERN unmodified")
+            .long_help(
+                "Leaves archive members with names matching regex PATTERN unmodified. --exclude-member takes precedence over --member when both patterns match a member name."
+            ),
+        Arg::with_name("skip-unknown")
+            .long("skip-unknown")
+            .help("Leaves archive members that are not objects unmodified")
+            .long_help(
+                "Leaves archive members that are not ELF or Mach-O objects, such as LLVM bitcode or text files, unmodified. By default, such members cause an error."
+            ),
+        Arg::with_name("hidden")
+            .long("hidden")
+            .takes_value(true)
+            .value_name("PATTERN")
+            .help("Sets all symbols with names matching regex PATTERN to hidden visibility"),
+        Arg::with_name("default")
+            .long("default")
+            .takes_value(true)
+            .value_name("PATTERN")
+            .help("Sets all symbols with names matching regex PATTERN to default visibility")
+            .long_help(
+                "Sets all symbols with names matching regex PATTERN to default visibility.  --default takes precedance over --hidden when both patterns match a symbol name.",
+            ),
+        Arg::with_name("strip")
+            .long("strip")
+            .takes_value(true)
+            .value_name("PATTERN")
+            .help("Removes all symbols with names matching regex PATTERN")
+            .long_help(
+                "Removes all symbols with names matching regex PATTERN from the symbol table. Fails if a matching symbol is referenced by a relocation, or if a matching global symbol is defined by an archive member, since the archive symbol table would still list it. Symbols are stripped before any other operation, so other patterns do not match stripped symbols."
+            ),
+        Arg::with_name("strip-locals")
+            .long("strip-locals")
+            .help("Removes all local symbols not referenced by relocations")
+            .long_help(
+                "Removes all local symbols not referenced by relocations from the symbol table, including Mach-O STABS debugging entries."
+            ),
+        Arg::with_name("INPUT")
+            .help("Path to source object or archive file, or - for standard input")
+            .required(true)
+            .index(1),
+        Arg::with_name("OUTPUT")
+            .help("Path to output file, or - for standard output")
+            .required(true)
+            .index(2),
+    ]
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("edit")
+        .about("Edits symbols in a binary or archive")
+        .long_about("Edits symbols in a binary or archive. This is the default when no subcommand is given.")
+        .args(&args())
+}
+
+/// Iterate over the pairs of values of an option that takes two values.
+fn value_pairs<'a>(
+    matches: &'a ArgMatches,
+    name: &str,
+) -> impl Iterator<Item = (&'a str, &'a str)> {
+    let values = matches.values_of(name).unwrap_or_default();
+    let original = values.clone().step_by(2);
+    let renamed = values.skip(1).step_by(2);
+    original.zip(renamed)
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input = matches.value_of("INPUT").unwrap();
+    let output = matches.value_of("OUTPUT").unwrap();
+
+    // Verbose output would be mixed into the binary written to standard output, so it is
+    // printed to standard error from the report instead
+    let to_stdout = output == "-";
+    let verbose = matches.is_present("verbose");
+    let editor = if input == "-" {
+        Editor::from_bytes(crate::read_input(input)?)
+    } else {
+        Editor::open(input)?
+    };
+    let mut editor = editor
+        .verbose(verbose && !to_stdout)
+        .verify(matches.is_present("verify"))
+        .mmap(matches.is_present("mmap"))
+        .skip_unknown(matches.is_present("skip-unknown"))
+        .require_match(matches.is_present("require-match"))
+        .deterministic(matches.is_present("deterministic"))
+        .rename_dwarf(matches.is_present("rename-dwarf"))
+        .strip_locals(matches.is_present("strip-locals"));
+    for pattern in matches.values_of("hidden").unwrap_or_default() {
+        editor = editor.hide(pattern);
+    }
+    for pattern in matches.values_of("default").unwrap_or_default() {
+        editor = editor.expose(pattern);
+    }
+    for pattern in matches.values_of("strip").unwrap_or_default() {
+        editor = editor.strip(pattern);
+    }
+    for pattern in matches.values_of("member").unwrap_or_default() {
+        editor = editor.member(pattern);
+    }
+    for pattern in matches.values_of("exclude-member").unwrap_or_default() {
+        editor = editor.exclude_member(pattern);
+    }
+    for (old, new) in value_pairs(matches, "rename") {
+        editor = editor.rename(old, new);
+    }
+    for (old, new) in value_pairs(matches, "rename-section") {
+        editor = editor.rename_section(old, new);
+    }
+    for (old, new) in value_pairs(matches, "rename-segment") {
+        editor = editor.rename_segment(old, new);
+    }
+    for (old, new) in value_pairs(matches, "rename-version") {
+        editor = editor.rename_version(old, new);
+    }
+    for (symbol, version) in value_pairs(matches, "set-symbol-version") {
+        editor = editor.set_symbol_version(symbol, version);
+    }
+    if let Some(soname) = matches.value_of("set-soname") {
+        editor = editor.set_soname(soname);
+    }
+    for (old, new) in value_pairs(matches, "replace-needed") {
+        editor = editor.replace_needed(old, new);
+    }
+    let report = if to_stdout {
+        let (data, report) = editor.into_bytes_with_report()?;
+        if verbose {
+            for change in report.objects.iter().flat_map(|object| &object.changes) {
+                eprintln!("{}", change);
+            }
+        }
+        std::io::stdout().lock().write_all(&data)?;
+        report
+    } else {
+        editor.write(output)?
+    };
+    for rule in report.unmatched() {
+        eprintln!("warning: {} did not match any names", rule);
+    }
+    if let Some(path) = matches.value_of("report") {
+        let json = serde_json::to_string_pretty(&report::to_json(&report))?;
+        std::fs::write(path, json + "\n")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! The `list` subcommand, which lists the symbols in a binary.
+
+use crate::report::context_json;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use symtool_backend::diff::SymbolInfo;
+use symtool_backend::error::Error;
+use symtool_backend::object::{inspect_object, Context, Object, TransformOptions};
+use symtool_backend::symbol::{symbols, Kind};
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("list")
+        .about("Lists the symbols in a binary or archive")
+        .long_about("Lists the named symbols in each object of a binary or archive, with their binding, kind, and visibility, and whether they are defined. Mach-O STABS debugging entries are not listed.")
+        .arg(
+            Arg::with_name("defined-only")
+                .long("defined-only")
+                .help("Only lists defined symbols"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Prints the symbols as JSON"),
+        )
+        .arg(
+            Arg::with_name("skip-unknown")
+                .long("skip-unknown")
+                .help("Ignores archive members that are not objects")
+                .long_help("Ignores archive members that are not ELF or Mach-O objects, such as LLVM bitcode or text files. By default, such members cause an error."),
+        )
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Path to object or archive file, or - for standard input")
+                .required(true)
+                .index(1),
+        )
+}
+
+fn symbol_json((symbol, defined): &(SymbolInfo, bool)) -> Value {
+    json!({
+        "name": symbol.name,
+        "binding": symbol.binding.to_string(),
+        "kind": symbol.kind.to_string(),
+        "visibility": symbol.visibility.to_string(),
+        "defined": defined,
+    })
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let options = TransformOptions {
+        skip_unknown: matches.is_present("skip-unknown"),
+        ..Default::default()
+    };
+    let defined_only = matches.is_present("defined-only");
+    let input = crate::read_input(matches.value_of("INPUT").unwrap())?;
+    let objects = inspect_object(
+        &input,
+        &options,
+        &|bytes: &[u8], object: Object, _: &Context| {
+            let mut listed = Vec::new();
+            for symbol in symbols(bytes, &object)? {
+                let name = match symbol.name() {
+                    Some(name) if !name.is_empty() => name.value,
+                    _ => continue,
+                };
+                if symbol.kind() == Kind::Debug || (defined_only && !symbol.is_defined()) {
+                    continue;
+                }
+                listed.push((SymbolInfo::new(name, &symbol), symbol.is_defined()));
+            }
+            Ok::<_, Error>(listed)
+        },
+    )?;
+    if matches.is_present("json") {
+        let objects: Vec<_> = objects
+            .iter()
+            .map(|(context, symbols)| {
+                let mut value = context_json(context);
+                value["symbols"] = symbols.iter().map(symbol_json).collect();
+                value
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "objects": objects }))?
+        );
+    } else {
+        for (context, symbols) in &objects {
+            if context.is_nested() {
+                println!("{}:", context);
+            }
+            for (symbol, defined) in symbols {
+                let defined = if *defined { "defined" } else { "undefined" };
+                println!(
+                    "{:9} {:6} {:8} {:9} {}",
+                    defined,
+                    symbol.binding.to_string(),
+                    symbol.kind.to_string(),
+                    symbol.visibility.to_string(),
+                    symbol.name
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! JSON reports of the changes made to a binary.
+
+use serde_json::{json, Value};
+use symtool_backend::object::{ArchiveKind, Context};
+use symtool_backend::report::{Change, Report, Rule};
+
+/// The command line option that added a rule, and its value.
+pub fn rule_option(rule: &Rule) -> (&'static str, &str) {
+    match rule {
+        Rule::Hide(pattern) => ("hidden", pattern),
+        Rule::Expose(pattern) => ("default", pattern),
+        Rule::Strip(pattern) => ("strip", pattern),
+        Rule::Rename(name) => ("rename", name),
+        Rule::RenameSection(name) => ("rename-section", name),
+        Rule::RenameSegment(name) => ("rename-segment", name),
+        Rule::RenameVersion(name) => ("rename-version", name),
+        Rule::SymbolVersion(name) => ("set-symbol-version", name),
+        Rule::Soname(name) => ("set-soname", name),
+        Rule::ReplaceNeeded(name) => ("replace-needed", name),
+    }
+}
+
+pub fn context_json(context: &Context) -> Value {
+    let archive = context.archive.map(|kind| match kind {
+        ArchiveKind::Gnu => "gnu",
+        ArchiveKind::Bsd => "bsd",
+    });
+    let member = context.member.as_ref();
+    json!({
+        "architecture": context.architecture(),
+        "archive": archive,
+        "member": member.map(|member| &member.name),
+        "index": member.map(|member| member.index),
+        "offset": member.map(|member| member.offset),
+    })
+}
+
+fn change_json(change: &Change) -> Value {
+    match change {
+        Change::StripSymbol { name } => json!({
+            "operation": "strip-symbol",
+            "symbol": name,
+        }),
+        Change::SetVisibility { name, old, new } => json!({
+            "operation": "set-visibility",
+            "symbol": name,
+            "old": old.to_string(),
+            "new": new.to_string(),
+        }),
+        Change::RenameSymbol { old, new } => json!({
+            "operation": "rename-symbol",
+            "old": old,
+            "new": new,
+        }),
+        Change::RenameSection { old, new } => json!({
+            "operation": "rename-section",
+            "old": old,
+            "new": new,
+        }),
+        Change::RenameSegment { old, new } => json!({
+            "operation": "rename-segment",
+            "old": old,
+            "new": new,
+        }),
+        Change::RenameVersion { old, new } => json!({
+            "operation": "rename-version",
+            "old": old,
+            "new": new,
+        }),
+        Change::SetSoname { old, new } => json!({
+            "operation": "set-soname",
+            "old": old,
+            "new": new,
+        }),
+        Change::ReplaceNeeded { old, new } => json!({
+            "operation": "replace-needed",
+            "old": old,
+            "new": new,
+        }),
+        Change::SetSymbolVersion { name, old, new } => json!({
+            "operation": "set-symbol-version",
+            "symbol": name,
+            "old": old,
+            "new": new,
+        }),
+    }
+}
+
+/// Convert a report to JSON.
+pub fn to_json(report: &Report) -> Value {
+    let objects: Vec<_> = report
+        .objects
+        .iter()
+        .map(|object| {
+            let mut value = context_json(&object.context);
+            value["changes"] = object.changes.iter().map(change_json).collect();
+            value
+        })
+        .collect();
+    let rules: Vec<_> = report
+        .rules
+        .iter()
+        .map(|(rule, matches)| {
+            let (option, value) = rule_option(rule);
+            json!({
+                "option": option,
+                "value": value,
+                "matches": matches,
+            })
+        })
+        .collect();
+    let unmatched = report.unmatched().count();
+    json!({
+        "objects": objects,
+        "rules": rules,
+        "matched": report.rules.len() - unmatched,
+        "unmatched": unmatched,
+    })
+}